@@ -0,0 +1,31 @@
+use crate::{
+    brains::safe_moves,
+    config::SnakeBrain,
+    game::{Direction, GameState, Position},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Heads straight for the closest food, only avoiding obstacles right in front of it.
+#[derive(Default, Deserialize, Serialize)]
+pub struct GreedyBrain {}
+
+#[typetag::serde(name = "greedy")]
+impl SnakeBrain for GreedyBrain {
+    fn next_direction(&self, game: &GameState, snake: usize) -> Direction {
+        let distance_to_food = |position: Position| {
+            game.foods
+                .iter()
+                .map(|food| {
+                    (food.x as i64 - position.x as i64).abs()
+                        + (food.y as i64 - position.y as i64).abs()
+                })
+                .min()
+                .unwrap_or(0)
+        };
+        safe_moves(game, snake)
+            .into_iter()
+            .min_by_key(|(_, position)| distance_to_food(*position))
+            .map_or(game.snakes[snake].direction, |(direction, _)| direction)
+    }
+}
@@ -0,0 +1,102 @@
+// Brains are registered with typetag, so they are only ever referred to by name in the config
+mod greedy;
+mod pathfinding;
+mod survival;
+
+use crate::{
+    game::{Direction, GameState, Position},
+    grid::Occupant,
+};
+
+use std::collections::{HashSet, VecDeque};
+
+fn is_passable(game: &GameState, position: Position) -> bool {
+    matches!(game.grid.get(position), Occupant::Empty | Occupant::Food)
+}
+
+/// Moves the snake can make next tick without immediately running into something,
+/// along with the position each of them leads to.
+fn safe_moves(game: &GameState, snake: usize) -> Vec<(Direction, Position)> {
+    let snake = &game.snakes[snake];
+    let head = match snake.body.front() {
+        Some(head) => *head,
+        None => return Vec::new(),
+    };
+    Direction::ALL
+        .iter()
+        .filter(|direction| **direction != snake.direction.opposite())
+        .filter_map(|direction| {
+            game.neighbour(head, *direction)
+                .map(|position| (*direction, position))
+        })
+        .filter(|(_, position)| is_passable(game, *position))
+        .collect()
+}
+
+/// Breadth-first search from the snake's head over passable cells,
+/// returning the first step of the shortest path to any cell matching `is_target`.
+fn first_step_towards(
+    game: &GameState,
+    snake: usize,
+    is_target: impl Fn(Position) -> bool,
+) -> Option<Direction> {
+    let head = *game.snakes[snake].body.front()?;
+    let reverse = game.snakes[snake].direction.opposite();
+    let mut visited = HashSet::new();
+    visited.insert(head);
+    let mut queue = VecDeque::new();
+    for direction in Direction::ALL {
+        if direction == reverse {
+            continue;
+        }
+        if let Some(position) = game.neighbour(head, direction) {
+            if is_passable(game, position) || is_target(position) {
+                visited.insert(position);
+                queue.push_back((position, direction));
+            }
+        }
+    }
+    while let Some((position, first_step)) = queue.pop_front() {
+        if is_target(position) {
+            return Some(first_step);
+        }
+        for direction in Direction::ALL {
+            if let Some(next) = game.neighbour(position, direction) {
+                if (is_passable(game, next) || is_target(next)) && visited.insert(next) {
+                    queue.push_back((next, first_step));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Counts the cells reachable from a position, up to `limit`.
+fn reachable_area(game: &GameState, from: Position, limit: usize) -> usize {
+    let mut visited = HashSet::new();
+    visited.insert(from);
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(position) = queue.pop_front() {
+        if visited.len() >= limit {
+            break;
+        }
+        for direction in Direction::ALL {
+            if let Some(next) = game.neighbour(position, direction) {
+                if is_passable(game, next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    visited.len()
+}
+
+/// The safe move leading into the most open space, or straight ahead if every move is fatal.
+fn roomiest_move(game: &GameState, snake: usize) -> Direction {
+    let limit = game.snakes[snake].body.len() * 2 + 1;
+    safe_moves(game, snake)
+        .into_iter()
+        .max_by_key(|(_, position)| reachable_area(game, *position, limit))
+        .map_or(game.snakes[snake].direction, |(direction, _)| direction)
+}
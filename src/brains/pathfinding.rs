@@ -0,0 +1,20 @@
+use crate::{
+    brains::{first_step_towards, roomiest_move},
+    config::SnakeBrain,
+    game::{Direction, GameState},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Follows the shortest path to the closest reachable food,
+/// and heads for open space when no food can be reached.
+#[derive(Default, Deserialize, Serialize)]
+pub struct PathfindingBrain {}
+
+#[typetag::serde(name = "pathfinding")]
+impl SnakeBrain for PathfindingBrain {
+    fn next_direction(&self, game: &GameState, snake: usize) -> Direction {
+        first_step_towards(game, snake, |position| game.foods.contains(&position))
+            .unwrap_or_else(|| roomiest_move(game, snake))
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{
+    brains::{first_step_towards, roomiest_move},
+    config::SnakeBrain,
+    game::{Direction, GameState},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Chases its own tail, which always moves out of the way in time,
+/// and only grows when food happens to lie along the way.
+#[derive(Default, Deserialize, Serialize)]
+pub struct SurvivalBrain {}
+
+#[typetag::serde(name = "survival")]
+impl SnakeBrain for SurvivalBrain {
+    fn next_direction(&self, game: &GameState, snake: usize) -> Direction {
+        let body = &game.snakes[snake].body;
+        // A snake this short has no tail to follow yet
+        if body.len() < 3 {
+            return roomiest_move(game, snake);
+        }
+        let tail = body[body.len() - 1];
+        first_step_towards(game, snake, |position| position == tail)
+            .unwrap_or_else(|| roomiest_move(game, snake))
+    }
+}
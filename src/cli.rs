@@ -0,0 +1,173 @@
+use std::env;
+
+pub const USAGE: &str = "\
+Usage:
+  hebi [options]              Play
+  hebi map export [options]   Print a generated map in the custom map format
+  hebi scores [options]       Print the high scores for the current settings
+
+Options:
+  --config <path>         Read the configuration from <path>
+  --replay <path>         Watch the replay in <path> (only when playing)
+  --strict                Refuse to start on configuration warnings
+  --seed <u64>            Set the seed, from 0 to 9223372036854775807
+  --map <file>            Play on a map read from <file>
+  --theme <name|path>     Set the theme
+  --tick-length <f64>     Set tick_length
+  --set <key>=<value>     Set any other option, written as in TOML
+  -h, --help              Print this help";
+
+/// Settings that only change how the game looks and sounds, which can be changed while watching a replay.
+const REPLAY_SETTINGS: &[&str] = &[
+    "theme",
+    "hud",
+    "grid_scale",
+    "eat_audio",
+    "destroy_audio",
+    "spawn_food_audio",
+    "spawn_snake_audio",
+    "strict",
+];
+
+pub enum Command {
+    /// `hebi map export`: prints a generated map in the custom map format, without opening a window
+    ExportMap,
+    /// `hebi scores`: prints the high scores for the current settings
+    Scores,
+    /// `hebi --help`: prints the usage
+    Help,
+}
+
+#[derive(Default)]
+pub struct Args {
+    pub command: Option<Command>,
+    pub config: Option<String>,
+    pub replay: Option<String>,
+    pub strict: bool,
+    pub seed: Option<u64>,
+    pub map: Option<String>,
+    /// Settings given on the command line, layered on top of the configuration file in order.
+    pub overrides: Vec<(String, toml::Value)>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut result = Self::default();
+        let mut args = env::args().skip(1).peekable();
+
+        if args.peek().map(String::as_str) == Some("map") {
+            args.next();
+            match args.next().as_deref() {
+                Some("export") => {}
+                Some(other) => return Err(format!("Unknown map command {:?}\n\n{}", other, USAGE)),
+                None => {
+                    return Err(format!(
+                        "Missing map command, expected \"export\"\n\n{}",
+                        USAGE
+                    ))
+                }
+            }
+            result.command = Some(Command::ExportMap);
+        } else if args.peek().map(String::as_str) == Some("scores") {
+            args.next();
+            result.command = Some(Command::Scores);
+        }
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+            match arg.as_str() {
+                "--config" => result.config = Some(value("--config")?),
+                "--replay" if result.command.is_none() => {
+                    result.replay = Some(value("--replay")?)
+                }
+                "--strict" => result.strict = true,
+                "--seed" => result.seed = Some(parse_seed(&value("--seed")?)?),
+                "--map" => result.map = Some(value("--map")?),
+                "--theme" => result.overrides.push((
+                    "theme".into(),
+                    toml::Value::String(value("--theme")?),
+                )),
+                "--tick-length" => {
+                    let tick_length = value("--tick-length")?;
+                    let tick_length = tick_length.parse().map_err(|error| {
+                        format!("Invalid tick length {:?}: {}", tick_length, error)
+                    })?;
+                    result
+                        .overrides
+                        .push(("tick_length".into(), toml::Value::Float(tick_length)));
+                }
+                "--set" => result.overrides.push(parse_override(&value("--set")?)?),
+                "-h" | "--help" => {
+                    result.command = Some(Command::Help);
+                    return Ok(result);
+                }
+                other => return Err(format!("Unknown argument {:?}\n\n{}", other, USAGE)),
+            }
+        }
+        if result.replay.is_some() {
+            result.check_replay_overrides()?;
+        }
+        Ok(result)
+    }
+
+    /// Replays are played back with the configuration they recorded, and anything that changes how
+    /// the game plays would make them play out differently.
+    fn check_replay_overrides(&self) -> Result<(), String> {
+        let mut changed = Vec::new();
+        if self.seed.is_some() {
+            changed.push("--seed".to_string());
+        }
+        if self.map.is_some() {
+            changed.push("--map".to_string());
+        }
+        for (key, _) in &self.overrides {
+            let setting = key.split('.').next().unwrap_or_default();
+            if !REPLAY_SETTINGS.contains(&setting) {
+                changed.push(key.clone());
+            }
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Can't change {} while watching a replay, it would make the replay play out differently",
+            changed.join(", ")
+        ))
+    }
+}
+
+/// Seeds are written to TOML in replays and high scores, where integers are signed,
+/// so they're kept within `0..=i64::MAX`.
+fn parse_seed(value: &str) -> Result<u64, String> {
+    let seed: u64 = value
+        .parse()
+        .map_err(|error| format!("Invalid seed {:?}: {}", value, error))?;
+    if seed > i64::MAX as u64 {
+        return Err(format!(
+            "Invalid seed {:?}: must be at most {}",
+            value,
+            i64::MAX
+        ));
+    }
+    Ok(seed)
+}
+
+/// Parses `key=value`, where the value is written as in TOML.
+/// Anything that isn't valid TOML is taken as a string, so `--set theme=cavern` works without quotes.
+fn parse_override(value: &str) -> Result<(String, toml::Value), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid setting {:?}, expected key=value", value))?;
+    let key = key.trim();
+    if key.split('.').any(str::is_empty) {
+        return Err(format!("Invalid setting name {:?}", key));
+    }
+    let value = toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.into()));
+    Ok((key.into(), value))
+}
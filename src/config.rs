@@ -1,115 +1,515 @@
-use crate::{maps::*, Direction};
+use crate::{
+    game::{Direction, GameState},
+    loading::ConfigError,
+    maps::*,
+};
 
-use bevy::prelude::KeyCode;
+use bevy::prelude::{Color, GamepadAxisType, GamepadButtonType, KeyCode};
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 use serde::{Deserialize, Serialize, Serializer};
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt,
+    str::FromStr,
+};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(default)]
 pub struct Config {
     pub theme: String,
     pub seed: u64,
     pub map: Box<dyn Map>,
-    pub controls: Controls,
+    pub players: Vec<Player>,
+    pub ai: Vec<Box<dyn SnakeBrain>>,
+    pub wrap: bool,
+    pub hud: Hud,
     pub grid_scale: u32,
     pub tick_length: f64,
     pub food_ticks: u32,
     pub snake_spawn_segments: u32,
     pub snake_segment_despawn_interval: f64,
     pub snake_respawn_delay: f64,
+    /// Whether dead snakes come back. Without respawning, the game is over once every player has died.
+    pub respawn: bool,
+    /// How many turns can be queued up for a snake, so that quick turns in a row each take a tick.
+    pub input_queue_length: usize,
     pub eat_audio: String,
     pub destroy_audio: String,
     pub spawn_food_audio: String,
     pub spawn_snake_audio: String,
+    pub replay_file: Option<String>,
+    pub strict: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             theme: "dracula".into(),
-            seed: random(),
+            // Kept within 32 bits so the seed can be written back to TOML, e.g. in replays
+            seed: random::<u32>().into(),
             map: Box::new(DefaultMap::default()),
-            controls: Default::default(),
+            players: vec![Default::default()],
+            ai: Vec::new(),
+            wrap: false,
+            hud: Default::default(),
             grid_scale: 36,
             tick_length: 0.2,
             food_ticks: 16,
             snake_spawn_segments: 2,
             snake_segment_despawn_interval: 0.1,
             snake_respawn_delay: 0.5,
+            respawn: true,
+            input_queue_length: 3,
             eat_audio: "eat.wav".into(),
             destroy_audio: "destroy.wav".into(),
             spawn_food_audio: "spawn_food.wav".into(),
             spawn_snake_audio: "spawn_snake.wav".into(),
+            replay_file: None,
+            strict: false,
         }
     }
 }
 
-#[derive(Deserialize)]
+impl Config {
+    /// Puts back the defaults of values the game can't run with, warning about each one.
+    pub fn sanitize(&mut self, file: &str) -> Vec<ConfigError> {
+        let defaults = Self::default();
+        let mut warnings = Vec::new();
+        let mut warn = |field: &str, problem: &str, default: &dyn fmt::Display| {
+            warnings.push(ConfigError::new(
+                file,
+                Some(field.into()),
+                format!("{}, using the default of {} instead", problem, default),
+            ));
+        };
+        if self.tick_length <= 0.0 || self.tick_length.is_nan() {
+            warn("tick_length", "Must be greater than 0", &defaults.tick_length);
+            self.tick_length = defaults.tick_length;
+        }
+        if self.food_ticks == 0 {
+            warn("food_ticks", "Must be at least 1", &defaults.food_ticks);
+            self.food_ticks = defaults.food_ticks;
+        }
+        if self.grid_scale == 0 {
+            warn("grid_scale", "Must be at least 1", &defaults.grid_scale);
+            self.grid_scale = defaults.grid_scale;
+        }
+        if self.snake_spawn_segments == 0 {
+            warn(
+                "snake_spawn_segments",
+                "Must be at least 1",
+                &defaults.snake_spawn_segments,
+            );
+            self.snake_spawn_segments = defaults.snake_spawn_segments;
+        }
+        if self.snake_segment_despawn_interval < 0.0 || self.snake_segment_despawn_interval.is_nan() {
+            warn(
+                "snake_segment_despawn_interval",
+                "Can't be negative",
+                &defaults.snake_segment_despawn_interval,
+            );
+            self.snake_segment_despawn_interval = defaults.snake_segment_despawn_interval;
+        }
+        if self.snake_respawn_delay < 0.0 || self.snake_respawn_delay.is_nan() {
+            warn(
+                "snake_respawn_delay",
+                "Can't be negative",
+                &defaults.snake_respawn_delay,
+            );
+            self.snake_respawn_delay = defaults.snake_respawn_delay;
+        }
+        if self.input_queue_length == 0 {
+            warn(
+                "input_queue_length",
+                "Must be at least 1",
+                &defaults.input_queue_length,
+            );
+            self.input_queue_length = defaults.input_queue_length;
+        }
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let controls = &mut player.controls;
+            let by_direction = [
+                ("up", &mut controls.up),
+                ("down", &mut controls.down),
+                ("left", &mut controls.left),
+                ("right", &mut controls.right),
+                ("clockwise", &mut controls.clockwise),
+                ("counter_clockwise", &mut controls.counter_clockwise),
+            ];
+            for (direction, bindings) in by_direction {
+                for (j, binding) in bindings.iter_mut().enumerate() {
+                    if let Binding::GamepadAxis { deadzone, .. } = binding {
+                        if !(0.0..1.0).contains(deadzone) {
+                            warn(
+                                &format!("players[{}].controls.{}[{}].deadzone", i, direction, j),
+                                "Must be at least 0 and less than 1",
+                                &DEFAULT_DEADZONE,
+                            );
+                            *deadzone = DEFAULT_DEADZONE;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(font_size) = self.hud.font_size {
+            if font_size <= 0.0 || font_size.is_nan() {
+                warnings.push(ConfigError::new(
+                    file,
+                    Some("hud.font_size".into()),
+                    "Must be greater than 0, using the default instead",
+                ));
+                self.hud.font_size = None;
+            }
+        }
+        warnings
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Hud {
+    pub placement: HudPlacement,
+    pub edge: HudEdge,
+    /// Defaults to half of `grid_scale`.
+    pub font_size: Option<f32>,
+}
+
+impl Hud {
+    pub fn font_size(&self, grid_scale: u32) -> f32 {
+        self.font_size.unwrap_or(grid_scale as f32 * 0.5)
+    }
+
+    /// Height of the HUD when showing `lines` lines of text.
+    pub fn height(&self, grid_scale: u32, lines: usize) -> f32 {
+        self.font_size(grid_scale) * (lines as f32 * 1.25 + 0.5)
+    }
+
+    /// Height of the strip reserved for the HUD outside the grid, if any.
+    pub fn strip_height(&self, grid_scale: u32, lines: usize) -> u32 {
+        match self.placement {
+            HudPlacement::Strip => self.height(grid_scale, lines).ceil() as u32,
+            HudPlacement::Overlay | HudPlacement::Hidden => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HudPlacement {
+    /// Drawn over the top or bottom row of the grid.
+    #[default]
+    Overlay,
+    /// Drawn in a strip above or below the grid, making the window taller.
+    Strip,
+    Hidden,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HudEdge {
+    #[default]
+    Top,
+    Bottom,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Player {
+    pub controls: Controls,
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(default)]
 pub struct Controls {
+    pub mode: ControlMode,
     pub up: Vec<Binding>,
     pub down: Vec<Binding>,
     pub left: Vec<Binding>,
     pub right: Vec<Binding>,
+    pub clockwise: Vec<Binding>,
+    pub counter_clockwise: Vec<Binding>,
+}
+
+/// Which of the bindings in `Controls` are used.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlMode {
+    /// `up`, `down`, `left` and `right` turn the snake to face that way.
+    #[default]
+    Absolute,
+    /// `clockwise` and `counter_clockwise` turn the snake relative to the way it's heading.
+    Relative,
 }
 
 impl Default for Controls {
     fn default() -> Self {
+        let left = vec![
+            Binding::Keyboard { key: KeyCode::Left },
+            Binding::Keyboard { key: KeyCode::A },
+            Binding::Keyboard { key: KeyCode::H },
+            Binding::GamepadButton {
+                button: GamepadButtonType::DPadLeft,
+                gamepad: None,
+            },
+            Binding::GamepadAxis {
+                axis: GamepadAxisType::LeftStickX,
+                direction: AxisDirection::Negative,
+                deadzone: DEFAULT_DEADZONE,
+                gamepad: None,
+            },
+        ];
+        let right = vec![
+            Binding::Keyboard { key: KeyCode::Right },
+            Binding::Keyboard { key: KeyCode::D },
+            Binding::Keyboard { key: KeyCode::L },
+            Binding::Keyboard { key: KeyCode::Numpad6 }, // numpad right with num lock
+            Binding::GamepadButton {
+                button: GamepadButtonType::DPadRight,
+                gamepad: None,
+            },
+            Binding::GamepadAxis {
+                axis: GamepadAxisType::LeftStickX,
+                direction: AxisDirection::Positive,
+                deadzone: DEFAULT_DEADZONE,
+                gamepad: None,
+            },
+        ];
         Self {
+            mode: ControlMode::Absolute,
             up: vec![
                 Binding::Keyboard { key: KeyCode::Up },
                 Binding::Keyboard { key: KeyCode::W },
                 Binding::Keyboard { key: KeyCode::K },
                 Binding::Keyboard { key: KeyCode::Numpad8 }, // numpad up with num lock
+                Binding::GamepadButton {
+                    button: GamepadButtonType::DPadUp,
+                    gamepad: None,
+                },
+                Binding::GamepadAxis {
+                    axis: GamepadAxisType::LeftStickY,
+                    direction: AxisDirection::Positive,
+                    deadzone: DEFAULT_DEADZONE,
+                    gamepad: None,
+                },
             ],
             down: vec![
                 Binding::Keyboard { key: KeyCode::Down },
                 Binding::Keyboard { key: KeyCode::S },
                 Binding::Keyboard { key: KeyCode::J },
                 Binding::Keyboard { key: KeyCode::Numpad2 }, // numpad down with num lock
+                Binding::GamepadButton {
+                    button: GamepadButtonType::DPadDown,
+                    gamepad: None,
+                },
+                Binding::GamepadAxis {
+                    axis: GamepadAxisType::LeftStickY,
+                    direction: AxisDirection::Negative,
+                    deadzone: DEFAULT_DEADZONE,
+                    gamepad: None,
+                },
             ],
-            left: vec![
-                Binding::Keyboard { key: KeyCode::Left },
-                Binding::Keyboard { key: KeyCode::A },
-                Binding::Keyboard { key: KeyCode::H },
-            ],
-            right: vec![
-                Binding::Keyboard { key: KeyCode::Right },
-                Binding::Keyboard { key: KeyCode::D },
-                Binding::Keyboard { key: KeyCode::L },
-                Binding::Keyboard { key: KeyCode::Numpad6 }, // numpad right with num lock
-            ],
+            // Relative controls turn the snake to its own left and right with the same keys
+            counter_clockwise: left
+                .iter()
+                .cloned()
+                .chain([Binding::GamepadButton {
+                    button: GamepadButtonType::LeftTrigger,
+                    gamepad: None,
+                }])
+                .collect(),
+            clockwise: right
+                .iter()
+                .cloned()
+                .chain([Binding::GamepadButton {
+                    button: GamepadButtonType::RightTrigger,
+                    gamepad: None,
+                }])
+                .collect(),
+            left,
+            right,
         }
     }
 }
 
-#[derive(Deserialize)]
+/// How far a stick has to be pushed, from 0 to 1, before it turns the snake.
+pub const DEFAULT_DEADZONE: f32 = 0.5;
+
+fn default_deadzone() -> f32 {
+    DEFAULT_DEADZONE
+}
+
+/// Gamepad bindings apply to every connected gamepad, unless `gamepad` picks one by id,
+/// counting up from 0 in the order the gamepads were connected.
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(tag = "device")]
 pub enum Binding {
     #[serde(rename = "keyboard")]
-    Keyboard { key: KeyCode }
+    Keyboard { key: KeyCode },
+    #[serde(rename = "gamepad_button")]
+    GamepadButton {
+        button: GamepadButtonType,
+        gamepad: Option<usize>,
+    },
+    /// Turns when the axis is pushed past the deadzone in `direction`.
+    #[serde(rename = "gamepad_axis")]
+    GamepadAxis {
+        axis: GamepadAxisType,
+        direction: AxisDirection,
+        #[serde(default = "default_deadzone")]
+        deadzone: f32,
+        gamepad: Option<usize>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDirection {
+    Positive,
+    Negative,
 }
 
-#[derive(Clone, Serialize)]
+/// Map cells, indexed by `(x, y)` with the y-axis pointing down.
+/// Serializes to the same text format that the custom map type reads.
+#[derive(Clone)]
 pub struct MapData {
     pub width: u32,
     pub height: u32,
-    #[serde(serialize_with = "serialize_cells")]
     pub cells: HashMap<(u32, u32), Cell>,
 }
 
-fn serialize_cells<S>(_cells: &HashMap<(u32, u32), Cell>, _s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    unimplemented!("Map data cells serialization is unimplemented!");
-}
-
 impl MapData {
+    /// How far a step in `direction` moves across the map. Unlike the game grid,
+    /// the y-axis points down, so up is towards row 0.
+    pub fn offset(direction: Direction) -> (i64, i64) {
+        let (dx, dy) = direction.offset();
+        (dx as i64, -dy as i64)
+    }
+
+    /// Iterates over the cells row by row, so that a game built from them plays out the same every run.
     pub fn iter(&self) -> impl Iterator<Item = (u32, u32, Cell)> + '_ {
-        self.cells.iter().map(|((x, y), cell)| (*x, *y, *cell))
+        (0..self.height).flat_map(move |y| {
+            (0..self.width)
+                .filter_map(move |x| self.cells.get(&(x, y)).map(|cell| (x, y, *cell)))
+        })
+    }
+
+    /// Mirrors the map left to right, turning spawns to match.
+    pub fn flipped_horizontally(&self) -> Self {
+        let width = self.width;
+        self.transformed(
+            (self.width, self.height),
+            |x, y| (width - 1 - x, y),
+            |direction| match direction {
+                Direction::Left | Direction::Right => direction.opposite(),
+                Direction::Up | Direction::Down => direction,
+            },
+        )
+    }
+
+    /// Mirrors the map top to bottom, turning spawns to match.
+    pub fn flipped_vertically(&self) -> Self {
+        let height = self.height;
+        self.transformed(
+            (self.width, self.height),
+            |x, y| (x, height - 1 - y),
+            |direction| match direction {
+                Direction::Up | Direction::Down => direction.opposite(),
+                Direction::Left | Direction::Right => direction,
+            },
+        )
+    }
+
+    /// Rotates the map a quarter turn clockwise, turning spawns to match.
+    /// The width and height swap places.
+    pub fn rotated_clockwise(&self) -> Self {
+        let height = self.height;
+        self.transformed(
+            (self.height, self.width),
+            |x, y| (height - 1 - y, x),
+            |direction| direction.clockwise(),
+        )
+    }
+
+    fn transformed(
+        &self,
+        (width, height): (u32, u32),
+        position: impl Fn(u32, u32) -> (u32, u32),
+        direction: impl Fn(Direction) -> Direction,
+    ) -> Self {
+        let cells = self
+            .iter()
+            .map(|(x, y, cell)| {
+                let cell = match cell {
+                    Cell::Spawn(spawn) => Cell::Spawn(direction(spawn)),
+                    cell => cell,
+                };
+                (position(x, y), cell)
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+}
+
+impl fmt::Display for MapData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = self.cells.get(&(x, y)).copied().unwrap_or(Cell::Empty);
+                write!(f, "{}", char::from(cell))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MapData {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        fn to_u32_in_range(value: usize, name: &str) -> Result<u32, String> {
+            value
+                .try_into()
+                .map_err(|_| format!("{} dimension is too big", name))
+        }
+
+        let mut cells = HashMap::new();
+        let mut width = 0u32;
+        let mut height = 0u32;
+
+        for (row, line) in value.lines().enumerate() {
+            let row = to_u32_in_range(row, "Vertical")?;
+
+            for (column, char) in line.chars().enumerate() {
+                let column = to_u32_in_range(column, "Horizontal")?;
+
+                cells.insert((column, row), Cell::try_from(char)?);
+
+                width = width.max(column + 1);
+                height = height.max(row + 1);
+            }
+        }
+
+        Ok(MapData {
+            width,
+            height,
+            cells,
+        })
+    }
+}
+
+impl Serialize for MapData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
     }
 }
 
@@ -120,13 +520,100 @@ pub enum Cell {
     Spawn(Direction),
 }
 
+impl From<Cell> for char {
+    fn from(cell: Cell) -> Self {
+        match cell {
+            Cell::Empty => ' ',
+            Cell::Wall => '#',
+            Cell::Spawn(Direction::Up) => '^',
+            Cell::Spawn(Direction::Down) => 'v',
+            Cell::Spawn(Direction::Left) => '<',
+            Cell::Spawn(Direction::Right) => '>',
+        }
+    }
+}
+
+impl TryFrom<char> for Cell {
+    type Error = String;
+
+    fn try_from(char: char) -> Result<Self, Self::Error> {
+        Ok(match char {
+            '#' => Cell::Wall,
+            '^' => Cell::Spawn(Direction::Up),
+            'v' => Cell::Spawn(Direction::Down),
+            '<' => Cell::Spawn(Direction::Left),
+            '>' => Cell::Spawn(Direction::Right),
+            ' ' => Cell::Empty,
+            other => return Err(format!("Unknown cell type {:?}", other)),
+        })
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(default)]
 pub struct Theme {
     pub walls: String,
     pub background: String,
     pub snake: String,
+    pub snakes: Vec<String>,
     pub food: Vec<String>,
+    /// Defaults to the color of the first snake.
+    pub hud_text: Option<String>,
+    /// Defaults to transparent, showing the grid or `background` behind the HUD.
+    pub hud_background: Option<String>,
+}
+
+impl Theme {
+    /// The first player's snake uses `snake`, any other players use `snakes` in order.
+    pub fn snake_color(&self, player: usize) -> &str {
+        match player {
+            0 => &self.snake,
+            _ if self.snakes.is_empty() => &self.snake,
+            _ => &self.snakes[(player - 1) % self.snakes.len()],
+        }
+    }
+
+    /// Warns about every color that isn't valid hex, since those are drawn in `MISSING_COLOR`.
+    pub fn check_colors(&self, file: &str) -> Vec<ConfigError> {
+        let mut colors = vec![
+            ("walls".to_string(), &self.walls),
+            ("background".to_string(), &self.background),
+            ("snake".to_string(), &self.snake),
+        ];
+        let optional = [
+            ("hud_text", &self.hud_text),
+            ("hud_background", &self.hud_background),
+        ];
+        for (name, color) in optional {
+            if let Some(color) = color {
+                colors.push((name.to_string(), color));
+            }
+        }
+        for (name, list) in [("snakes", &self.snakes), ("food", &self.food)] {
+            for (i, color) in list.iter().enumerate() {
+                colors.push((format!("{}[{}]", name, i), color));
+            }
+        }
+        let mut warnings: Vec<ConfigError> = colors
+            .into_iter()
+            .filter(|(_, color)| Color::hex(color).is_err())
+            .map(|(field, color)| {
+                ConfigError::new(
+                    file,
+                    Some(field),
+                    format!("{:?} is not a valid hex color", color),
+                )
+            })
+            .collect();
+        if self.food.is_empty() {
+            warnings.push(ConfigError::new(
+                file,
+                Some("food".into()),
+                "No food colors are given",
+            ));
+        }
+        warnings
+    }
 }
 
 impl Default for Theme {
@@ -136,7 +623,10 @@ impl Default for Theme {
             walls: DEFAULT_COLOR.into(),
             background: DEFAULT_COLOR.into(),
             snake: DEFAULT_COLOR.into(),
+            snakes: Vec::new(),
             food: vec![DEFAULT_COLOR.into()],
+            hud_text: None,
+            hud_background: None,
         }
     }
 }
@@ -146,3 +636,17 @@ pub trait Map {
     fn get_map_data(&self, generator: &mut Pcg64) -> MapData;
     fn get_dimensions(&self) -> (u32, u32);
 }
+
+#[typetag::serde(tag = "type")]
+pub trait Modifier {
+    /// Transforms the map data generated by a map, or by the modifiers before this one.
+    fn modify(&self, map_data: MapData, generator: &mut Pcg64) -> MapData;
+    /// The dimensions that a map of the given dimensions has after being modified.
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32);
+}
+
+#[typetag::serde(tag = "type")]
+pub trait SnakeBrain {
+    /// Decides which way the computer-controlled snake should turn on the next tick.
+    fn next_direction(&self, game: &GameState, snake: usize) -> Direction;
+}
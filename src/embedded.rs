@@ -0,0 +1,33 @@
+/// The bundled themes, compiled in so that the executable works on its own.
+/// Theme files on disk take precedence over these.
+const THEMES: &[(&str, &str)] = &[
+    ("cavern", include_str!("../themes/cavern.toml")),
+    ("dracula", include_str!("../themes/dracula.toml")),
+    ("solarized_dark", include_str!("../themes/solarized_dark.toml")),
+    ("solarized_light", include_str!("../themes/solarized_light.toml")),
+];
+
+/// The default sounds, which sound files on disk take precedence over in the same way.
+const SOUNDS: &[(&str, &[u8])] = &[
+    ("destroy.wav", include_bytes!("../assets/sounds/destroy.wav")),
+    ("eat.wav", include_bytes!("../assets/sounds/eat.wav")),
+    ("spawn_food.wav", include_bytes!("../assets/sounds/spawn_food.wav")),
+    ("spawn_snake.wav", include_bytes!("../assets/sounds/spawn_snake.wav")),
+];
+
+/// The HUD's font, which unlike the themes and sounds can't be replaced.
+pub const FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+pub fn theme(name: &str) -> Option<&'static str> {
+    THEMES
+        .iter()
+        .find(|(theme, _)| *theme == name)
+        .map(|(_, contents)| *contents)
+}
+
+pub fn sound(name: &str) -> Option<&'static [u8]> {
+    SOUNDS
+        .iter()
+        .find(|(sound, _)| *sound == name)
+        .map(|(_, bytes)| *bytes)
+}
@@ -0,0 +1,693 @@
+use crate::{
+    config::{Cell, Config, Map, MapData},
+    grid::{Grid, Occupant},
+};
+
+use rand::prelude::*;
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Deserialize, Serialize)]
+pub enum Direction {
+    Left,
+    Right,
+    Down,
+    Up,
+}
+
+impl Direction {
+    pub const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Down, Self::Up];
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Down => Self::Up,
+            Self::Up => Self::Down,
+        }
+    }
+    /// The direction after a quarter turn clockwise, as seen on screen.
+    pub fn clockwise(&self) -> Self {
+        match self {
+            Self::Left => Self::Up,
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+        }
+    }
+    pub fn counter_clockwise(&self) -> Self {
+        self.clockwise().opposite()
+    }
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+            Self::Down => (0, -1),
+            Self::Up => (0, 1),
+        }
+    }
+}
+
+/// A cell on the game grid. Unlike `MapData`, the y-axis points up.
+#[derive(Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Position {
+    pub fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SpawnPosition {
+    pub position: Position,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Input {
+    pub snake: usize,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    SnakeSpawned { snake: usize },
+    SnakeMoved { snake: usize },
+    /// Carries the snake's score and length as they were when it died,
+    /// since the snake may already have respawned by the time the event is read.
+    SnakeDied {
+        snake: usize,
+        score: u32,
+        length: usize,
+    },
+    FoodSpawned { position: Position, variant: u32 },
+    FoodEaten { position: Position },
+}
+
+pub struct Snake {
+    pub direction: Direction,
+    /// Turns waiting to be made, one per tick, oldest first.
+    pub queued_directions: VecDeque<Direction>,
+    /// Positions of every segment, head first. Empty while the snake is dead.
+    pub body: VecDeque<Position>,
+    pub score: u32,
+    growth: u32,
+    respawn_ticks: Option<u32>,
+}
+
+impl Snake {
+    fn new() -> Self {
+        Self {
+            direction: Direction::Up,
+            queued_directions: VecDeque::new(),
+            body: VecDeque::new(),
+            score: 0,
+            growth: 0,
+            respawn_ticks: Some(0),
+        }
+    }
+    pub fn is_alive(&self) -> bool {
+        !self.body.is_empty()
+    }
+    /// The direction the snake will be heading in once it has made every queued turn.
+    pub fn heading(&self) -> Direction {
+        self.queued_directions
+            .back()
+            .copied()
+            .unwrap_or(self.direction)
+    }
+    /// Queues a turn, unless the queue is full or the turn would repeat or reverse the one before it.
+    fn queue_turn(&mut self, direction: Direction, queue_length: usize) {
+        let previous = self.heading();
+        if direction != previous
+            && direction != previous.opposite()
+            && self.queued_directions.len() < queue_length
+        {
+            self.queued_directions.push_back(direction);
+        }
+    }
+    /// Whether the snake is dead and won't respawn.
+    pub fn is_out(&self) -> bool {
+        !self.is_alive() && self.respawn_ticks.is_none()
+    }
+}
+
+struct Random {
+    snake_spawn_generator: Pcg64,
+    food_spawn_generator: Pcg64,
+    environment_generator: Pcg64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        let generator = || Pcg64::seed_from_u64(seed);
+        Random {
+            snake_spawn_generator: generator(),
+            food_spawn_generator: generator(),
+            environment_generator: generator(),
+        }
+    }
+}
+
+/// Generates a map the same way a game with the given seed does.
+pub fn generate_map(map: &dyn Map, seed: u64) -> MapData {
+    map.get_map_data(&mut Random::new(seed).environment_generator)
+}
+
+/// The complete game rules, independent of rendering and input handling.
+/// Every call to `step` advances the game by one tick.
+pub struct GameState {
+    pub width: u32,
+    pub height: u32,
+    pub grid: Grid,
+    pub spawn_positions: Vec<SpawnPosition>,
+    pub snakes: Vec<Snake>,
+    pub foods: Vec<Position>,
+    pub tick: u64,
+    pub wrap: bool,
+    random: Random,
+    players: usize,
+    tick_length: f64,
+    food_ticks: u32,
+    snake_spawn_segments: u32,
+    snake_segment_despawn_interval: f64,
+    snake_respawn_delay: f64,
+    respawn: bool,
+    input_queue_length: usize,
+}
+
+impl GameState {
+    pub fn new(config: &Config, map_data: &MapData) -> Self {
+        let random = Random::new(config.seed);
+
+        let mut grid = Grid::new(map_data.width, map_data.height);
+        let mut spawn_positions = Vec::new();
+        let top = map_data.height - 1;
+        for (x, y, cell) in map_data.iter() {
+            let position = Position::new(x, top - y);
+            match cell {
+                Cell::Empty => {}
+                Cell::Wall => grid.set(position, Occupant::Wall),
+                Cell::Spawn(direction) => spawn_positions.push(SpawnPosition {
+                    position,
+                    direction,
+                }),
+            }
+        }
+        // Map cells are stored in a HashMap, whose iteration order changes between runs
+        spawn_positions.sort_by_key(|spawn_position| spawn_position.position);
+
+        Self {
+            width: map_data.width,
+            height: map_data.height,
+            grid,
+            spawn_positions,
+            // Players come first, followed by computer-controlled snakes
+            snakes: (0..config.players.len() + config.ai.len())
+                .map(|_| Snake::new())
+                .collect(),
+            foods: Vec::new(),
+            tick: 0,
+            wrap: config.wrap,
+            random,
+            players: config.players.len(),
+            tick_length: config.tick_length,
+            food_ticks: config.food_ticks,
+            snake_spawn_segments: config.snake_spawn_segments,
+            snake_segment_despawn_interval: config.snake_segment_despawn_interval,
+            snake_respawn_delay: config.snake_respawn_delay,
+            respawn: config.respawn,
+            input_queue_length: config.input_queue_length,
+        }
+    }
+
+    /// Applies the timing and spawning settings, which unlike the rest can change mid-game.
+    pub fn reconfigure(&mut self, config: &Config) {
+        self.tick_length = config.tick_length;
+        self.food_ticks = config.food_ticks;
+        self.snake_spawn_segments = config.snake_spawn_segments;
+        self.snake_segment_despawn_interval = config.snake_segment_despawn_interval;
+        self.snake_respawn_delay = config.snake_respawn_delay;
+        self.respawn = config.respawn;
+        self.input_queue_length = config.input_queue_length;
+    }
+
+    /// Whether every player is out for good, which can only happen without respawning.
+    /// A game without players is over once every computer-controlled snake is out.
+    pub fn is_over(&self) -> bool {
+        let snakes = match self.players {
+            0 => &self.snakes[..],
+            players => &self.snakes[..players],
+        };
+        !snakes.is_empty() && snakes.iter().all(Snake::is_out)
+    }
+
+    pub fn step(&mut self, inputs: &[Input]) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+
+        for input in inputs {
+            if let Some(snake) = self.snakes.get_mut(input.snake) {
+                if snake.is_alive() {
+                    snake.queue_turn(input.direction, self.input_queue_length);
+                }
+            }
+        }
+
+        self.tick += 1;
+        self.move_snakes(&mut events);
+        self.respawn_snakes(&mut events);
+        if self.food_ticks > 0 && self.tick.is_multiple_of(self.food_ticks as u64) {
+            self.spawn_food(&mut events);
+        }
+
+        events
+    }
+
+    /// Returns the neighbouring position in the given direction,
+    /// or `None` if that position lies outside of the grid.
+    /// On a wrapping board, positions past an edge come back in on the opposite edge.
+    pub fn neighbour(&self, position: Position, direction: Direction) -> Option<Position> {
+        let (dx, dy) = direction.offset();
+        let x = position.x as i64 + dx as i64;
+        let y = position.y as i64 + dy as i64;
+        if self.wrap {
+            return Some(Position::new(
+                x.rem_euclid(self.width as i64) as u32,
+                y.rem_euclid(self.height as i64) as u32,
+            ));
+        }
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return None;
+        }
+        Some(Position::new(x as u32, y as u32))
+    }
+
+    fn move_snakes(&mut self, events: &mut Vec<GameEvent>) {
+        let mut dead = Vec::new();
+        let mut moves = Vec::new();
+        // Snakes that die keep the length they had before their tail moved
+        let lengths: Vec<usize> = self.snakes.iter().map(|snake| snake.body.len()).collect();
+        for index in 0..self.snakes.len() {
+            if !self.snakes[index].is_alive() {
+                continue;
+            }
+            let head = self.snakes[index].body[0];
+            let snake = &mut self.snakes[index];
+            let direction = snake.queued_directions.pop_front().unwrap_or(snake.direction);
+            snake.direction = direction;
+            match self.neighbour(head, direction) {
+                Some(next) => moves.push((index, next)),
+                // Moved off the edge of the grid
+                None => dead.push(index),
+            }
+        }
+
+        // Tails move out of the way before any head moves in,
+        // so a head may follow directly behind a tail.
+        for (index, _) in moves.iter() {
+            let snake = &mut self.snakes[*index];
+            if snake.growth > 0 {
+                snake.growth -= 1;
+            } else if let Some(tail) = snake.body.pop_back() {
+                self.grid.set(tail, Occupant::Empty);
+            }
+        }
+
+        for (index, next) in moves.iter() {
+            let collided = match self.grid.get(*next) {
+                Occupant::Wall | Occupant::Snake(_) => true,
+                Occupant::Empty | Occupant::Food => false,
+            };
+            // Heads moving into the same cell take each other out
+            let head_on = moves
+                .iter()
+                .any(|(other_index, other_next)| other_index != index && other_next == next);
+            if collided || head_on {
+                dead.push(*index);
+            }
+        }
+
+        for (index, next) in moves {
+            if dead.contains(&index) {
+                continue;
+            }
+            let ate = self.grid.get(next) == Occupant::Food;
+            self.grid.set(next, Occupant::Snake(index));
+            self.snakes[index].body.push_front(next);
+            events.push(GameEvent::SnakeMoved { snake: index });
+            if ate {
+                self.eat_food(index, next, events);
+            }
+        }
+
+        for index in dead {
+            self.kill_snake(index, lengths[index], events);
+        }
+    }
+
+    fn kill_snake(&mut self, index: usize, length: usize, events: &mut Vec<GameEvent>) {
+        let snake = &mut self.snakes[index];
+        for position in snake.body.drain(..) {
+            self.grid.set(position, Occupant::Empty);
+        }
+        snake.growth = 0;
+        snake.queued_directions.clear();
+        events.push(GameEvent::SnakeDied {
+            snake: index,
+            score: snake.score,
+            length,
+        });
+        if !self.respawn {
+            snake.respawn_ticks = None;
+            return;
+        }
+        // Give the renderer time to play the despawn animation before respawning
+        let respawn_time = self.snake_segment_despawn_interval * length.saturating_sub(1) as f64
+            + self.snake_respawn_delay;
+        snake.respawn_ticks = Some(if self.tick_length > 0.0 {
+            (respawn_time / self.tick_length).ceil() as u32
+        } else {
+            0
+        });
+    }
+
+    fn eat_food(&mut self, index: usize, position: Position, events: &mut Vec<GameEvent>) {
+        self.foods.retain(|food| *food != position);
+        let snake = &mut self.snakes[index];
+        snake.growth += 1;
+        snake.score += 1;
+        events.push(GameEvent::FoodEaten { position });
+    }
+
+    fn respawn_snakes(&mut self, events: &mut Vec<GameEvent>) {
+        for index in 0..self.snakes.len() {
+            match self.snakes[index].respawn_ticks {
+                Some(0) => self.spawn_snake(index, events),
+                Some(ticks) => self.snakes[index].respawn_ticks = Some(ticks - 1),
+                None => {}
+            }
+        }
+    }
+
+    /// Lays out a snake's body from a spawn position, trailing behind the spawn direction.
+    /// Returns `None` if the body doesn't fit on the grid or would overlap a wall or snake.
+    fn spawn_body(&self, spawn_position: &SpawnPosition) -> Option<VecDeque<Position>> {
+        let mut body = VecDeque::new();
+        body.push_back(spawn_position.position);
+        let behind = spawn_position.direction.opposite();
+        for _ in 1..self.snake_spawn_segments {
+            body.push_back(self.neighbour(*body.back().unwrap(), behind)?);
+        }
+        body.iter()
+            .all(|position| matches!(self.grid.get(*position), Occupant::Empty | Occupant::Food))
+            .then_some(body)
+    }
+
+    fn spawn_snake(&mut self, index: usize, events: &mut Vec<GameEvent>) {
+        // Skip spawn positions currently blocked by another snake,
+        // and try again next tick if all of them are.
+        let available: Vec<(SpawnPosition, VecDeque<Position>)> = self
+            .spawn_positions
+            .iter()
+            .filter_map(|spawn_position| {
+                self.spawn_body(spawn_position)
+                    .map(|body| (*spawn_position, body))
+            })
+            .collect();
+        let (spawn_position, body) =
+            match available.choose(&mut self.random.snake_spawn_generator) {
+                Some(spawn) => spawn.clone(),
+                None => return,
+            };
+        let snake = &mut self.snakes[index];
+        snake.direction = spawn_position.direction;
+        snake.queued_directions.clear();
+        snake.score = 0;
+        snake.respawn_ticks = None;
+        events.push(GameEvent::SnakeSpawned { snake: index });
+        for position in body {
+            // Food underneath a freshly spawned snake counts as eaten
+            if self.grid.get(position) == Occupant::Food {
+                self.eat_food(index, position, events);
+            }
+            self.grid.set(position, Occupant::Snake(index));
+            self.snakes[index].body.push_back(position);
+        }
+    }
+
+    fn spawn_food(&mut self, events: &mut Vec<GameEvent>) {
+        let generator = &mut self.random.food_spawn_generator;
+        // Spawn no food if the entire grid is full
+        let position = match self.grid.choose_free(generator) {
+            Some(position) => position,
+            None => return,
+        };
+        // Random value used by the renderer to pick a color out of the theme
+        let variant = generator.next_u32();
+        self.grid.set(position, Occupant::Food);
+        self.foods.push(position);
+        events.push(GameEvent::FoodSpawned { position, variant });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Starts a game with one snake per spawn, without food unless placed by the test,
+    /// and steps it once so that the snakes spawn.
+    fn start(map: &str, config: Config) -> GameState {
+        let map_data: MapData = map.parse().unwrap();
+        let spawns = map_data
+            .iter()
+            .filter(|(_, _, cell)| matches!(cell, Cell::Spawn(_)))
+            .count();
+        let config = Config {
+            seed: 0,
+            players: (0..spawns).map(|_| Default::default()).collect(),
+            food_ticks: 0,
+            ..config
+        };
+        let mut game = GameState::new(&config, &map_data);
+        let events = game.step(&[]);
+        assert_eq!(spawned(&events), spawns);
+        game
+    }
+
+    fn spawned(events: &[GameEvent]) -> usize {
+        events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::SnakeSpawned { .. }))
+            .count()
+    }
+
+    fn died(events: &[GameEvent]) -> Vec<(usize, u32, usize)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::SnakeDied {
+                    snake,
+                    score,
+                    length,
+                } => Some((*snake, *score, *length)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn turn(direction: Direction) -> Input {
+        Input {
+            snake: 0,
+            direction,
+        }
+    }
+
+    #[test]
+    fn snakes_move_and_turn() {
+        let mut game = start(
+            "#####\n\
+             #   #\n\
+             # ^ #\n\
+             #   #\n\
+             #####",
+            Config::default(),
+        );
+        // Map rows count down from the top, grid rows up from the bottom
+        assert_eq!(
+            game.snakes[0].body,
+            [Position::new(2, 2), Position::new(2, 1)]
+        );
+
+        let events = game.step(&[]);
+        assert!(matches!(events[..], [GameEvent::SnakeMoved { snake: 0 }]));
+        assert_eq!(
+            game.snakes[0].body,
+            [Position::new(2, 3), Position::new(2, 2)]
+        );
+        assert_eq!(game.grid.get(Position::new(2, 1)), Occupant::Empty);
+        assert_eq!(game.grid.get(Position::new(2, 3)), Occupant::Snake(0));
+
+        game.step(&[turn(Direction::Left)]);
+        assert_eq!(game.snakes[0].direction, Direction::Left);
+        assert_eq!(
+            game.snakes[0].body,
+            [Position::new(1, 3), Position::new(2, 3)]
+        );
+    }
+
+    #[test]
+    fn snakes_ignore_turning_back_on_themselves() {
+        let mut game = start(
+            "#####\n\
+             #   #\n\
+             # ^ #\n\
+             #   #\n\
+             #####",
+            Config::default(),
+        );
+        game.step(&[turn(Direction::Down)]);
+        assert_eq!(game.snakes[0].direction, Direction::Up);
+        assert!(game.snakes[0].is_alive());
+    }
+
+    #[test]
+    fn eating_food_scores_and_grows_the_snake() {
+        let mut game = start(
+            "#####\n\
+             #   #\n\
+             #   #\n\
+             # ^ #\n\
+             #   #\n\
+             #####",
+            Config::default(),
+        );
+        let food = Position::new(2, 3);
+        game.grid.set(food, Occupant::Food);
+        game.foods.push(food);
+
+        let events = game.step(&[]);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, GameEvent::FoodEaten { position } if *position == food)));
+        assert_eq!(game.snakes[0].score, 1);
+        assert!(game.foods.is_empty());
+        assert_eq!(game.snakes[0].body.len(), 2);
+
+        // The tail stays put for a tick to make the snake one longer
+        game.step(&[]);
+        assert_eq!(
+            game.snakes[0].body,
+            [
+                Position::new(2, 4),
+                Position::new(2, 3),
+                Position::new(2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn snakes_die_on_walls() {
+        let mut game = start(
+            "###\n\
+             #^#\n\
+             # #\n\
+             ###",
+            Config {
+                respawn: false,
+                ..Default::default()
+            },
+        );
+        let events = game.step(&[]);
+        assert_eq!(died(&events), [(0, 0, 2)]);
+        assert!(game.snakes[0].is_out());
+        assert!(game.is_over());
+        assert_eq!(game.grid.get(Position::new(1, 2)), Occupant::Empty);
+        assert_eq!(game.grid.get(Position::new(1, 1)), Occupant::Empty);
+    }
+
+    #[test]
+    fn snakes_die_leaving_the_board() {
+        let mut game = start(
+            " ^ \n   ",
+            Config {
+                respawn: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(died(&game.step(&[])), [(0, 0, 2)]);
+    }
+
+    #[test]
+    fn snakes_die_running_into_themselves() {
+        let mut game = start(
+            "#####\n\
+             #   #\n\
+             # ^ #\n\
+             #   #\n\
+             #   #\n\
+             #   #\n\
+             #   #\n\
+             #####",
+            Config {
+                snake_spawn_segments: 5,
+                respawn: false,
+                ..Default::default()
+            },
+        );
+        let turns = [Direction::Right, Direction::Down, Direction::Left];
+        assert!(died(&game.step(&turns.map(turn))).is_empty());
+        assert!(died(&game.step(&[])).is_empty());
+        assert_eq!(died(&game.step(&[])), [(0, 0, 5)]);
+    }
+
+    #[test]
+    fn snakes_take_each_other_out_head_on() {
+        let mut game = start(
+            "#######\n\
+             # > < #\n\
+             #######",
+            Config {
+                respawn: false,
+                ..Default::default()
+            },
+        );
+        let mut events = died(&game.step(&[]));
+        events.sort_unstable();
+        assert_eq!(events, [(0, 0, 2), (1, 0, 2)]);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn snakes_respawn_after_dying() {
+        let mut game = start(
+            "###\n\
+             #^#\n\
+             # #\n\
+             ###",
+            Config::default(),
+        );
+        game.snakes[0].score = 3;
+        assert_eq!(died(&game.step(&[])), [(0, 3, 2)]);
+        assert!(!game.snakes[0].is_out());
+        assert!(!game.is_over());
+
+        let mut ticks = 0;
+        while !game.snakes[0].is_alive() {
+            ticks += 1;
+            assert!(ticks <= 10, "the snake never respawned");
+            game.step(&[]);
+        }
+        assert_eq!(game.snakes[0].score, 0);
+        assert_eq!(game.snakes[0].direction, Direction::Up);
+        assert_eq!(
+            game.snakes[0].body,
+            [Position::new(1, 2), Position::new(1, 1)]
+        );
+    }
+}
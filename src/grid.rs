@@ -0,0 +1,164 @@
+use crate::game::Position;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Occupant {
+    Empty,
+    Wall,
+    Food,
+    Snake(usize),
+}
+
+/// Occupancy of every cell on the board, indexed by `(x, y)`.
+/// Keeps a set of free cells alongside so food can be placed without rejection sampling.
+pub struct Grid {
+    pub width: u32,
+    pub height: u32,
+    cells: Vec<Occupant>,
+    free_cells: Vec<Position>,
+    // Index of each cell inside `free_cells`, if it is free
+    free_indices: Vec<Option<usize>>,
+}
+
+impl Grid {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        let mut free_cells = Vec::with_capacity(size);
+        let mut free_indices = Vec::with_capacity(size);
+        for y in 0..height {
+            for x in 0..width {
+                free_indices.push(Some(free_cells.len()));
+                free_cells.push(Position::new(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            cells: vec![Occupant::Empty; size],
+            free_cells,
+            free_indices,
+        }
+    }
+
+    fn index(&self, position: Position) -> Option<usize> {
+        if position.x < self.width && position.y < self.height {
+            Some((position.y * self.width + position.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns what occupies the given cell. Cells outside of the grid count as walls.
+    pub fn get(&self, position: Position) -> Occupant {
+        match self.index(position) {
+            Some(index) => self.cells[index],
+            None => Occupant::Wall,
+        }
+    }
+
+    pub fn set(&mut self, position: Position, occupant: Occupant) {
+        let index = match self.index(position) {
+            Some(index) => index,
+            None => return,
+        };
+        let previous = std::mem::replace(&mut self.cells[index], occupant);
+        match (previous == Occupant::Empty, occupant == Occupant::Empty) {
+            (true, false) => {
+                let free_index = self.free_indices[index].take().unwrap();
+                self.free_cells.swap_remove(free_index);
+                if let Some(moved) = self.free_cells.get(free_index) {
+                    let moved_index = self.index(*moved).unwrap();
+                    self.free_indices[moved_index] = Some(free_index);
+                }
+            }
+            (false, true) => {
+                self.free_indices[index] = Some(self.free_cells.len());
+                self.free_cells.push(position);
+            }
+            _ => {}
+        }
+    }
+
+    /// Picks a free cell uniformly at random, or `None` if the grid is full.
+    pub fn choose_free<R: Rng + ?Sized>(&self, generator: &mut R) -> Option<Position> {
+        self.free_cells.choose(generator).copied()
+    }
+
+    pub fn walls(&self) -> impl Iterator<Item = Position> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, occupant)| **occupant == Occupant::Wall)
+            .map(move |(index, _)| {
+                Position::new(index as u32 % self.width, index as u32 / self.width)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    #[test]
+    fn cells_outside_the_grid_are_walls() {
+        let mut grid = Grid::new(3, 2);
+        assert_eq!(grid.get(Position::new(3, 0)), Occupant::Wall);
+        assert_eq!(grid.get(Position::new(0, 2)), Occupant::Wall);
+        // Setting them does nothing
+        grid.set(Position::new(3, 0), Occupant::Food);
+        assert_eq!(grid.get(Position::new(3, 0)), Occupant::Wall);
+        assert_eq!(grid.walls().count(), 0);
+    }
+
+    #[test]
+    fn choose_free_only_picks_empty_cells() {
+        let mut generator = Pcg64::seed_from_u64(0);
+        let mut grid = Grid::new(3, 3);
+        let free = Position::new(1, 2);
+        for y in 0..3 {
+            for x in 0..3 {
+                if Position::new(x, y) != free {
+                    grid.set(Position::new(x, y), Occupant::Wall);
+                }
+            }
+        }
+        for _ in 0..20 {
+            assert_eq!(grid.choose_free(&mut generator), Some(free));
+        }
+        assert_eq!(grid.walls().count(), 8);
+    }
+
+    #[test]
+    fn choose_free_picks_cells_freed_again() {
+        let mut generator = Pcg64::seed_from_u64(0);
+        let mut grid = Grid::new(2, 2);
+        let positions = [
+            Position::new(0, 0),
+            Position::new(1, 0),
+            Position::new(0, 1),
+            Position::new(1, 1),
+        ];
+        for (index, position) in positions.iter().enumerate() {
+            grid.set(*position, Occupant::Snake(index));
+        }
+        assert_eq!(grid.choose_free(&mut generator), None);
+
+        // Replacing one occupant with another keeps the cell taken
+        grid.set(positions[0], Occupant::Food);
+        assert_eq!(grid.choose_free(&mut generator), None);
+
+        grid.set(positions[2], Occupant::Empty);
+        for _ in 0..20 {
+            assert_eq!(grid.choose_free(&mut generator), Some(positions[2]));
+        }
+        // Freeing a cell twice doesn't make it twice as likely to be picked
+        grid.set(positions[2], Occupant::Empty);
+        grid.set(positions[2], Occupant::Wall);
+        assert_eq!(grid.choose_free(&mut generator), None);
+    }
+}
@@ -0,0 +1,197 @@
+use serde::de::DeserializeOwned;
+use serde_path_to_error::Segment;
+use std::{fmt, fs, io};
+
+/// A problem with a configuration or theme file.
+pub struct ConfigError {
+    pub file: String,
+    /// 1-based line and column, when the problem can be pinned down to one.
+    pub position: Option<(usize, usize)>,
+    /// Path to the offending value, such as `map.corridor_width` or `players[0].controls`.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(file: &str, field: Option<String>, message: impl Into<String>) -> Self {
+        Self {
+            file: file.into(),
+            position: None,
+            field,
+            message: message.into(),
+        }
+    }
+
+    fn from_toml(file: &str, field: Option<String>, error: &toml::de::Error) -> Self {
+        // toml appends the key and location to its messages, but those are kept separately here
+        let message = error.to_string();
+        let end = [" for key `", " at line "]
+            .iter()
+            .filter_map(|suffix| message.find(suffix))
+            .min()
+            .unwrap_or(message.len());
+        Self {
+            file: file.into(),
+            position: error
+                .line_col()
+                .map(|(line, column)| (line + 1, column + 1)),
+            field,
+            message: message[..end].into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, ": `{}`", field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Whatever could be read from a file, along with everything that was wrong with it.
+pub struct Parsed<T> {
+    pub value: T,
+    /// The top-level keys that made it into `value`.
+    pub table: toml::value::Table,
+    pub errors: Vec<ConfigError>,
+    pub warnings: Vec<ConfigError>,
+}
+
+impl<T: Default> Parsed<T> {
+    pub fn failed(error: ConfigError) -> Self {
+        Self {
+            value: Default::default(),
+            table: Default::default(),
+            errors: vec![error],
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Settings layered on top of a file, as dotted keys and the values to put there.
+pub type Overrides = [(String, toml::Value)];
+
+/// Reads and parses a TOML file. A missing file is only an error if it isn't `optional`.
+pub fn load<T: DeserializeOwned + Default>(
+    path: &str,
+    optional: bool,
+    overrides: &Overrides,
+) -> Parsed<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => parse(path, &contents, overrides),
+        Err(error) if optional && error.kind() == io::ErrorKind::NotFound => {
+            parse(path, "", overrides)
+        }
+        Err(error) => Parsed::failed(ConfigError::new(
+            path,
+            None,
+            format!("Failed to load file: {}", error),
+        )),
+    }
+}
+
+/// Parses TOML leniently: a top-level key that fails to parse is reported and left out,
+/// so it falls back to its default instead of taking the rest of the file down with it.
+/// Keys that aren't used by `T` are reported as warnings.
+pub fn parse<T: DeserializeOwned + Default>(
+    file: &str,
+    contents: &str,
+    overrides: &Overrides,
+) -> Parsed<T> {
+    let (mut table, mut errors) = match toml::from_str(contents) {
+        Ok(table) => (table, Vec::new()),
+        Err(error) => (
+            toml::value::Table::new(),
+            vec![ConfigError::from_toml(file, None, &error)],
+        ),
+    };
+    for (key, value) in overrides {
+        set(&mut table, key, value.clone());
+    }
+    // Positions only make sense while parsing the file as written
+    let mut reserialized = !errors.is_empty() || !overrides.is_empty();
+    let mut contents = if reserialized {
+        serialize(&table)
+    } else {
+        contents.to_string()
+    };
+    loop {
+        let mut unknown_keys = Vec::new();
+        let mut deserializer = toml::Deserializer::new(&contents);
+        let result = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+            &mut deserializer,
+            &mut |path: serde_ignored::Path| unknown_keys.push(path.to_string()),
+        ));
+        match result {
+            Ok(value) => {
+                let warnings = unknown_keys
+                    .into_iter()
+                    .map(|key| ConfigError::new(file, Some(key), "Unknown key, it is ignored"))
+                    .collect();
+                return Parsed {
+                    value,
+                    table,
+                    errors,
+                    warnings,
+                };
+            }
+            Err(error) => {
+                let field = error.path().iter().next().map(|_| error.path().to_string());
+                let key = match error.path().iter().next() {
+                    Some(Segment::Map { key }) if table.contains_key(key) => Some(key.clone()),
+                    _ => None,
+                };
+                let mut config_error = ConfigError::from_toml(file, field, error.inner());
+                if reserialized {
+                    config_error.position = None;
+                }
+                errors.push(config_error);
+                let key = match key {
+                    Some(key) => key,
+                    None => {
+                        return Parsed {
+                            value: Default::default(),
+                            table: Default::default(),
+                            errors,
+                            warnings: Vec::new(),
+                        }
+                    }
+                };
+                table.remove(&key);
+                contents = serialize(&table);
+                reserialized = true;
+            }
+        }
+    }
+}
+
+fn serialize(table: &toml::value::Table) -> String {
+    toml::to_string(&toml::Value::Table(table.clone()))
+        .expect("A parsed TOML table can always be serialized again")
+}
+
+/// Sets a dotted key such as `map.corridor_width`, creating tables along the way.
+fn set(table: &mut toml::value::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        None => {
+            table.insert(key.into(), value);
+        }
+        Some((first, rest)) => {
+            let entry = table
+                .entry(first)
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(Default::default());
+            }
+            if let toml::Value::Table(inner) = entry {
+                set(inner, rest, value);
+            }
+        }
+    }
+}
@@ -1,27 +1,63 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod brains;
+mod cli;
 mod config;
+mod embedded;
+mod game;
+mod grid;
+mod loading;
 mod maps;
+mod modifiers;
+mod paths;
+mod replay;
+mod scores;
+mod validation;
+mod watch;
 
+use crate::cli::{Args, Command};
 use crate::config::*;
-use bevy::core::FixedTimestep;
+use crate::game::{generate_map, Direction, GameEvent, GameState, Input, Position};
+use crate::loading::{ConfigError, Parsed};
+use crate::paths::SearchPaths;
+use crate::replay::{Replay, ReplayMode};
+use crate::scores::{gameplay_settings, today, HighScores, Run};
+use crate::validation::{validate_map, MapIssue};
+use crate::watch::FileWatcher;
+use bevy::app::AppExit;
+use bevy::ecs::schedule::ShouldRun;
 use bevy::input::keyboard::KeyboardInput;
 use bevy::input::ElementState;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
-use rand::prelude::*;
-use rand::seq::SliceRandom;
-use rand_pcg::Pcg64;
-use serde::de::DeserializeOwned;
-use std::fs;
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::WindowCloseRequested;
+use rand::random;
+use std::{collections::BTreeSet, fs, path::PathBuf, process};
 
 const TITLE: &str = "Hebi";
 const MISSING_COLOR: Color = Color::FUCHSIA;
+/// How many new seeds restarting tries before giving up on finding a map that can be played.
+const RESTART_ATTEMPTS: u32 = 10;
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
 enum Labels {
-    Moving,
-    Respawning,
+    Ticking,
+    Restarting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum AppState {
+    Title,
+    Playing,
+    /// Ticks are held back, freezing the snakes along with their respawn timers.
+    Paused,
+    /// Every player is out for good, which only happens when `respawn` is off.
+    GameOver,
+}
+
+/// Starts a new game on a freshly generated map, either with the same seed or a new one.
+struct Restart {
+    new_seed: bool,
 }
 
 struct GridDimensions {
@@ -30,15 +66,83 @@ struct GridDimensions {
     scale: u32,
 }
 
+/// Gamepad buttons count as pressed past this value, like Bevy's default press threshold.
+const BUTTON_PRESS_THRESHOLD: f32 = 0.75;
+
+/// A turn asked for by a player, which becomes an `Input` on the next tick.
+#[derive(Clone, Copy)]
+struct PlayerInput {
+    snake: usize,
+    turn: Turn,
+}
+
+#[derive(Clone, Copy)]
+enum Turn {
+    Towards(Direction),
+    /// Relative to the way the snake will be heading once it has made its queued turns.
+    Clockwise,
+    CounterClockwise,
+}
+
 struct DirectionalControls {
-    scan_codes: HashMap<KeyCode, Direction>,
+    scan_codes: HashMap<KeyCode, Vec<PlayerInput>>,
+    /// Inputs for each button, along with the gamepad they are limited to, if any.
+    gamepad_buttons: HashMap<GamepadButtonType, Vec<(Option<usize>, PlayerInput)>>,
+    gamepad_axes: HashMap<GamepadAxisType, Vec<AxisControl>>,
+}
+
+/// A turn made by pushing an axis past its deadzone.
+struct AxisControl {
+    gamepad: Option<usize>,
+    direction: AxisDirection,
+    deadzone: f32,
+    input: PlayerInput,
+}
+
+impl AxisControl {
+    fn is_pushed(&self, value: f32) -> bool {
+        match self.direction {
+            AxisDirection::Positive => value > self.deadzone,
+            AxisDirection::Negative => value < -self.deadzone,
+        }
+    }
 }
 
 impl DirectionalControls {
-    fn from_keyboard(&self, event: &KeyboardInput) -> Option<Direction> {
+    fn from_keyboard(&self, event: &KeyboardInput) -> &[PlayerInput] {
         event.key_code
             .and_then(|code| self.scan_codes.get(&code))
-            .cloned()
+            .map_or(&[], |inputs| inputs.as_slice())
+    }
+
+    fn from_gamepad_button(
+        &self,
+        gamepad: Gamepad,
+        button: GamepadButtonType,
+    ) -> impl Iterator<Item = PlayerInput> + '_ {
+        self.gamepad_buttons
+            .get(&button)
+            .into_iter()
+            .flatten()
+            .filter(move |(id, _)| id.is_none_or(|id| id == gamepad.0))
+            .map(|(_, input)| *input)
+    }
+
+    /// Only turns when the axis is first pushed past a deadzone, not for as long as it stays there.
+    fn from_gamepad_axis(
+        &self,
+        gamepad: Gamepad,
+        axis: GamepadAxisType,
+        previous: f32,
+        value: f32,
+    ) -> impl Iterator<Item = PlayerInput> + '_ {
+        self.gamepad_axes
+            .get(&axis)
+            .into_iter()
+            .flatten()
+            .filter(move |control| control.gamepad.is_none_or(|id| id == gamepad.0))
+            .filter(move |control| control.is_pushed(value) && !control.is_pushed(previous))
+            .map(|control| control.input)
     }
 }
 
@@ -50,20 +154,59 @@ impl FromWorld for DirectionalControls {
 
         let mut result = Self {
             scan_codes: Default::default(),
+            gamepad_buttons: Default::default(),
+            gamepad_axes: Default::default(),
         };
 
-        let by_direction = [
-            (Direction::Up, &config.controls.up),
-            (Direction::Down, &config.controls.down),
-            (Direction::Left, &config.controls.left),
-            (Direction::Right, &config.controls.right),
-        ];
-
-        for (direction, bindings) in by_direction {
-            for binding in bindings {
-                match binding {
-                    Binding::Keyboard { key } => {
-                        result.scan_codes.insert(*key, direction);
+        for (snake, player) in config.players.iter().enumerate() {
+            let controls = &player.controls;
+            let by_turn = match controls.mode {
+                ControlMode::Absolute => vec![
+                    (Turn::Towards(Direction::Up), &controls.up),
+                    (Turn::Towards(Direction::Down), &controls.down),
+                    (Turn::Towards(Direction::Left), &controls.left),
+                    (Turn::Towards(Direction::Right), &controls.right),
+                ],
+                ControlMode::Relative => vec![
+                    (Turn::Clockwise, &controls.clockwise),
+                    (Turn::CounterClockwise, &controls.counter_clockwise),
+                ],
+            };
+
+            for (turn, bindings) in by_turn {
+                for binding in bindings {
+                    match binding {
+                        Binding::Keyboard { key } => {
+                            result
+                                .scan_codes
+                                .entry(*key)
+                                .or_default()
+                                .push(PlayerInput { snake, turn });
+                        }
+                        Binding::GamepadButton { button, gamepad } => {
+                            result
+                                .gamepad_buttons
+                                .entry(*button)
+                                .or_default()
+                                .push((*gamepad, PlayerInput { snake, turn }));
+                        }
+                        Binding::GamepadAxis {
+                            axis,
+                            direction,
+                            deadzone,
+                            gamepad,
+                        } => {
+                            result
+                                .gamepad_axes
+                                .entry(*axis)
+                                .or_default()
+                                .push(AxisControl {
+                                    gamepad: *gamepad,
+                                    direction: *direction,
+                                    deadzone: *deadzone,
+                                    input: PlayerInput { snake, turn },
+                                });
+                        }
                     }
                 }
             }
@@ -73,139 +216,1213 @@ impl FromWorld for DirectionalControls {
     }
 }
 
-struct Random {
-    snake_spawn_generator: Pcg64,
-    food_spawn_generator: Pcg64,
-    environment_generator: Pcg64,
+/// What gamepads were last seen doing, to tell presses apart from buttons and sticks being held.
+#[derive(Default)]
+struct GamepadStates {
+    pressed_buttons: HashSet<(usize, GamepadButtonType)>,
+    axes: HashMap<(usize, GamepadAxisType), f32>,
+}
+
+/// Inputs received since the last tick, waiting to be passed to `GameState::step`.
+#[derive(Default)]
+struct PendingInputs {
+    inputs: Vec<PlayerInput>,
+}
+
+/// Runs the game tick every `tick_length` seconds like Bevy's `FixedTimestep`,
+/// except that the tick length can be changed while the game is running.
+struct TickTimer {
+    tick_length: f64,
+    accumulator: f64,
+    /// Seconds spent playing since the game was started, not counting pauses.
+    elapsed: f64,
 }
 
-impl Random {
-    fn new(config: &Config) -> Self {
-        let generator = || Pcg64::seed_from_u64(config.seed);
-        Random {
-            snake_spawn_generator: generator(),
-            food_spawn_generator: generator(),
-            environment_generator: generator(),
+/// The configuration and theme files, watched so that changes can be applied while the game runs.
+struct HotReload {
+    args: Args,
+    config_watcher: Option<FileWatcher>,
+    theme_watcher: Option<FileWatcher>,
+    /// The configuration as last loaded, to tell which settings changed.
+    config_table: toml::value::Table,
+    timer: Timer,
+}
+
+/// Stats shown on the HUD that outlast a single life, indexed the same way as `GameState::snakes`.
+#[derive(Default)]
+struct SessionStats {
+    best_scores: Vec<u32>,
+    deaths: Vec<u32>,
+}
+
+/// The high-score table, along with the players' runs in progress.
+struct HighScoreTracking {
+    path: PathBuf,
+    high_scores: HighScores,
+    settings: toml::value::Table,
+    seed: u64,
+    players: usize,
+    /// When each snake's current run started and how long the snake got, indexed like `GameState::snakes`.
+    runs: Vec<Option<(f64, usize)>>,
+    /// Where the runs that ended in this game placed on the high scores, for the game over screen.
+    places: Vec<Option<usize>>,
+}
+
+impl HighScoreTracking {
+    /// Records the runs of the given snakes that are still going, saving the high scores if any of them placed.
+    /// Each snake is given as its index, along with the score and length it ended on.
+    fn end_runs(
+        &mut self,
+        ended: impl IntoIterator<Item = (usize, u32, usize)>,
+        game: &GameState,
+        now: f64,
+    ) {
+        self.places.resize(game.snakes.len(), None);
+        let mut changed = false;
+        for (snake, score, length) in ended {
+            let (started, longest) = match self.runs.get_mut(snake).and_then(Option::take) {
+                Some(run) => run,
+                None => continue,
+            };
+            if score == 0 {
+                continue;
+            }
+            let length = length.max(longest);
+            let run = Run {
+                score,
+                length,
+                date: today(),
+                seed: self.seed,
+                duration: now - started,
+            };
+            if let Some(place) = self.high_scores.record(&self.settings, run) {
+                changed = true;
+                self.places[snake] = Some(place);
+                eprintln!(
+                    "P{} got #{} on the high scores with {} points",
+                    snake + 1,
+                    place,
+                    score
+                );
+            }
         }
+        if changed {
+            if let Err(error) = self.high_scores.save(&self.path) {
+                eprintln!("{}", error);
+            }
+        }
+    }
+}
+
+/// The font the HUD and menus are written in.
+struct UiFont(Handle<Font>);
+
+impl FromWorld for UiFont {
+    fn from_world(world: &mut World) -> Self {
+        let font =
+            Font::try_from_bytes(embedded::FONT.to_vec()).expect("The built-in font is valid");
+        let mut fonts = world
+            .get_resource_mut::<Assets<Font>>()
+            .expect("Missing font assets to add the built-in font to");
+        Self(fonts.add(font))
     }
 }
 
+/// Sprites of every snake, indexed the same way as `GameState::snakes`, head first.
+#[derive(Default)]
+struct SnakeSprites {
+    snakes: Vec<Vec<Entity>>,
+}
+
 fn main() {
-    fn read_toml_file<T: DeserializeOwned + Default>(path: &str) -> T {
-        let result = fs::read_to_string(path)
-            .map_err(|error| format!("Failed to load {:?}: {}", path, error))
-            .and_then(|contents| {
-                toml::from_str(&contents)
-                    .map_err(|error| format!("Failed to parse {:?}: {}", path, error))
-            });
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
 
-        match result {
-            Ok(value) => value,
-            Err(error) => {
-                eprintln!("{}", error);
-                Default::default()
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::ExportMap => export_map(&args),
+            Command::Scores => print_scores(&args),
+            Command::Help => {
+                println!("{}", cli::USAGE);
+                Ok(())
             }
+        };
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            process::exit(1);
         }
+        return;
     }
 
-    let config: Config = read_toml_file("config.toml");
-    let theme: Theme = read_toml_file(&format!("themes/{}.toml", config.theme));
+    let playback = args.replay.as_ref().map(|path| match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    });
 
-    let (grid_width, grid_height) = config.map.get_dimensions();
+    let paths = SearchPaths::new();
+    let Parsed {
+        value: config,
+        table: loaded_table,
+        mut errors,
+        mut warnings,
+    } = match load_config(&args, &paths, playback.as_ref()) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    let (theme_file, theme) = load_theme(&paths, &config.theme);
+    errors.extend(theme.errors);
+    warnings.extend(theme.warnings);
+    let theme = theme.value;
+
+    if report_config_errors(&errors, &warnings, args.strict || config.strict) {
+        eprintln!("Refusing to start in strict mode");
+        process::exit(1);
+    }
+    if !errors.is_empty() {
+        eprintln!("Using defaults for the settings above, pass --strict to refuse to start instead");
+    }
+
+    let map_data = generate_map(&*config.map, config.seed);
+    let issues = validate_map(&map_data, config.snake_spawn_segments, config.wrap);
+    if report_map_issues(&issues) {
+        process::exit(1);
+    }
+
+    let replay_mode = match (playback, &config.replay_file) {
+        (Some(replay), _) => ReplayMode::Playback(replay),
+        (None, Some(path)) => match Replay::new(&config, &map_data) {
+            Ok(replay) => ReplayMode::Recording {
+                path: path.clone(),
+                replay,
+            },
+            Err(error) => {
+                eprintln!("{}, the game won't be recorded", error);
+                ReplayMode::Off
+            }
+        },
+        (None, None) => ReplayMode::Off,
+    };
+
+    let game = GameState::new(&config, &map_data);
+    let (grid_width, grid_height) = (game.width, game.height);
 
     let grid_scale = config.grid_scale;
+    let tick_length = config.tick_length;
+    // Watching a replay doesn't count as playing
+    let high_scores = match (&replay_mode, paths.high_scores()) {
+        (ReplayMode::Playback(_), _) | (_, None) => None,
+        (_, Some(path)) => match HighScores::load(&path) {
+            Ok(high_scores) => Some(HighScoreTracking {
+                path,
+                high_scores,
+                settings: gameplay_settings(&config),
+                seed: config.seed,
+                players: config.players.len(),
+                runs: Vec::new(),
+                places: Vec::new(),
+            }),
+            Err(error) => {
+                eprintln!("{}, high scores won't be kept", error);
+                None
+            }
+        },
+    };
+    let hud_strip = config
+        .hud
+        .strip_height(grid_scale, hud_lines(game.snakes.len()));
+    let hot_reload = HotReload {
+        // The configuration of a replay comes from the replay, so it can't change
+        config_watcher: match replay_mode {
+            ReplayMode::Playback(_) => None,
+            _ => config_file(&args, &paths).map(FileWatcher::new),
+        },
+        theme_watcher: theme_file.map(FileWatcher::new),
+        config_table: loaded_table,
+        timer: Timer::from_seconds(0.5, true),
+        args,
+    };
 
-    App::new()
+    let mut app = App::new();
+    app.add_state(AppState::Title)
         .add_startup_system(setup)
-        .add_system(snake_movement_input)
-        .add_system(despawning.before(Labels::Moving))
+        .add_startup_system(hud_setup)
+        .add_startup_system(overlay_setup)
+        .add_system(menu_input.before(Labels::Restarting))
+        .add_system(restarting.label(Labels::Restarting).before(Labels::Ticking))
         .add_system_set(
-            SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(config.tick_length))
-                .with_system(snake_movement.label(Labels::Moving))
-                .with_system(
-                    snake_respawn
-                        
-                        .label(Labels::Respawning)
-                        .after(Labels::Moving),
-                )
-                .with_system(snake_eating.after(Labels::Moving))
-                .with_system(snake_collision_check.after(Labels::Moving)),
+            SystemSet::on_update(AppState::Playing)
+                .with_system(game_over_checking.after(Labels::Ticking)),
         )
-        .add_system(snake_spawn)
+        .add_system(snake_movement_input.before(Labels::Ticking))
+        .add_system(despawning)
+        .add_system(hot_reloading.before(Labels::Ticking))
         .add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(
-                    config.tick_length * config.food_ticks as f64,
-                ))
-                .with_system(food_spawn),
+                .with_run_criteria(tick_timing)
+                .with_system(game_tick.label(Labels::Ticking)),
         )
+        .add_system(snake_rendering.after(Labels::Ticking))
+        .add_system(food_rendering.after(Labels::Ticking))
+        .add_system(replay_saving.after(Labels::Ticking))
+        .add_system(recoloring.after(Labels::Ticking))
+        .add_system(hud_updating.after(Labels::Ticking))
+        .add_system(overlay_updating.after(Labels::Ticking))
+        .add_system(high_score_tracking.after(Labels::Ticking))
         .add_system_to_stage(CoreStage::PostUpdate, grid_positioning)
         .insert_resource(WindowDescriptor {
             title: TITLE.to_string(),
             width: (grid_width * config.grid_scale) as f32,
-            height: (grid_height * config.grid_scale) as f32,
+            height: (grid_height * config.grid_scale + hud_strip) as f32,
             resizable: false,
             ..Default::default()
         })
         .insert_resource(ClearColor(
             Color::hex(&theme.background).unwrap_or(MISSING_COLOR),
         ))
-        .insert_resource(Respawn::default())
-        .insert_resource(Random::new(&config))
+        .insert_resource(game)
+        .insert_resource(PendingInputs::default())
+        .insert_resource(replay_mode)
+        .insert_resource(SnakeSprites::default())
+        .insert_resource(SessionStats::default())
         .insert_non_send_resource(config)
         .insert_resource(theme)
+        .insert_resource(paths)
+        .insert_resource(hot_reload)
+        .insert_resource(TickTimer {
+            tick_length,
+            accumulator: 0.0,
+            elapsed: 0.0,
+        })
         .insert_resource(GridDimensions {
             width: grid_width,
             height: grid_height,
             scale: grid_scale,
         })
         .init_resource::<DirectionalControls>()
-        .add_event::<RespawnEvent>()
+        .add_event::<GameEvent>()
+        .add_event::<Restart>()
         .add_plugins(DefaultPlugins)
-        .run();
+        .init_resource::<UiFont>();
+    if let Some(high_scores) = high_scores {
+        app.insert_resource(high_scores);
+    }
+    app.run();
+}
+
+/// Loads the configuration of the replay being played back, or else the configuration file,
+/// with the settings given on the command line layered on top.
+fn load_config(
+    args: &Args,
+    paths: &SearchPaths,
+    playback: Option<&Replay>,
+) -> Result<Parsed<Config>, String> {
+    let mut overrides = args.overrides.clone();
+    if let Some(path) = &args.map {
+        overrides.push(("map".into(), read_map_file(path)?));
+    }
+    let (file, mut config): (String, Parsed<Config>) = match (playback, &args.replay) {
+        (Some(replay), Some(path)) => {
+            let contents = toml::to_string(&replay.config)
+                .map_err(|error| format!("Failed to read replay {:?}: {}", path, error))?;
+            (path.clone(), loading::parse(path, &contents, &overrides))
+        }
+        _ => match config_file(args, paths) {
+            Some(path) => {
+                let config = loading::load(&path, false, &overrides);
+                (path, config)
+            }
+            // Without a configuration file, the settings are layered on the defaults
+            None => (
+                "config.toml".into(),
+                loading::parse("config.toml", "", &overrides),
+            ),
+        },
+    };
+    if let Some(seed) = args.seed {
+        config.value.seed = seed;
+    }
+    let warnings = config.value.sanitize(&file);
+    config.warnings.extend(warnings);
+    Ok(config)
+}
+
+/// The configuration file to read: the one given on the command line,
+/// or else the first one found in the search paths.
+fn config_file(args: &Args, paths: &SearchPaths) -> Option<String> {
+    args.config.clone().or_else(|| {
+        paths
+            .config()
+            .map(|path| path.to_string_lossy().into_owned())
+    })
+}
+
+/// Reads a map given with `--map`: either a map in the custom map format,
+/// or a `.toml` file with the same settings as the `[map]` table of the configuration.
+fn read_map_file(path: &str) -> Result<toml::Value, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("Failed to load map {:?}: {}", path, error))?;
+    if path.ends_with(".toml") {
+        return toml::from_str(&contents)
+            .map_err(|error| format!("Failed to parse map {:?}: {}", path, error));
+    }
+    let mut map = toml::value::Table::new();
+    map.insert("type".into(), toml::Value::String("custom".into()));
+    map.insert("data".into(), toml::Value::String(contents));
+    Ok(toml::Value::Table(map))
+}
+
+/// Themes are given either by name, which is looked up in the search paths,
+/// or as a path to a `.toml` file.
+fn find_theme(paths: &SearchPaths, theme: &str) -> Result<String, ConfigError> {
+    if theme.ends_with(".toml") {
+        return Ok(theme.into());
+    }
+    match paths.theme(theme) {
+        Some(path) => Ok(path.to_string_lossy().into_owned()),
+        None => {
+            let directories: Vec<String> = paths
+                .directories()
+                .map(|directory| directory.join("themes").display().to_string())
+                .collect();
+            Err(ConfigError::new(
+                &format!("themes/{}.toml", theme),
+                None,
+                format!("Theme not found in {}", directories.join(", ")),
+            ))
+        }
+    }
+}
+
+/// Finds and loads a theme, along with the file it was loaded from,
+/// falling back to the built-in theme of the same name.
+fn load_theme(paths: &SearchPaths, theme: &str) -> (Option<String>, Parsed<Theme>) {
+    match find_theme(paths, theme) {
+        Ok(file) => {
+            let mut theme: Parsed<Theme> = loading::load(&file, false, &[]);
+            let warnings = theme.value.check_colors(&file);
+            theme.warnings.extend(warnings);
+            (Some(file), theme)
+        }
+        Err(error) => match embedded::theme(theme) {
+            Some(contents) => {
+                let file = format!("built-in theme {:?}", theme);
+                (None, loading::parse(&file, contents, &[]))
+            }
+            None => (None, Parsed::failed(error)),
+        },
+    }
+}
+
+fn export_map(args: &Args) -> Result<(), String> {
+    let config = load_config(args, &SearchPaths::new(), None)?;
+    let strict = args.strict || config.value.strict;
+    let refused = report_config_errors(&config.errors, &config.warnings, strict);
+    if refused || !config.errors.is_empty() {
+        return Err("Failed to load the configuration".into());
+    }
+    let config = config.value;
+    eprintln!("Generating map with seed {}", config.seed);
+    let map_data = generate_map(&*config.map, config.seed);
+    report_map_issues(&validate_map(
+        &map_data,
+        config.snake_spawn_segments,
+        config.wrap,
+    ));
+    print!("{}", map_data);
+    Ok(())
+}
+
+fn print_scores(args: &Args) -> Result<(), String> {
+    let paths = SearchPaths::new();
+    let config = load_config(args, &paths, None)?;
+    report_config_errors(&config.errors, &config.warnings, false);
+    let path = paths
+        .high_scores()
+        .ok_or("Can't tell where high scores are kept")?;
+    let high_scores = HighScores::load(&path)?;
+    let settings = gameplay_settings(&config.value);
+    let table = match high_scores.table(&settings) {
+        Some(table) if !table.runs.is_empty() => table,
+        _ => {
+            println!("No high scores with these settings yet");
+            return Ok(());
+        }
+    };
+    for (i, run) in table.runs.iter().enumerate() {
+        println!(
+            "{:>2}. {:>4} points   length {:>3}   {:>4.0}s   seed {}   {}",
+            i + 1,
+            run.score,
+            run.length,
+            run.duration,
+            run.seed,
+            run.date
+        );
+    }
+    Ok(())
+}
+
+/// Prints every problem found with the configuration,
+/// returning whether any of them should stop the game from starting.
+/// Strict mode refuses to start on warnings as well as errors.
+fn report_config_errors(errors: &[ConfigError], warnings: &[ConfigError], strict: bool) -> bool {
+    for error in errors {
+        eprintln!("Error: {}", error);
+    }
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    strict && !(errors.is_empty() && warnings.is_empty())
+}
+
+/// Prints every issue found with a map, returning whether any of them are errors.
+fn report_map_issues(issues: &[MapIssue]) -> bool {
+    for issue in issues {
+        let severity = if issue.is_error() { "Error" } else { "Warning" };
+        eprintln!("{}: {}", severity, issue);
+    }
+    issues.iter().any(MapIssue::is_error)
 }
 
 fn setup(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut audio_sources: ResMut<Assets<AudioSource>>,
     asset_server: Res<AssetServer>,
     config: NonSend<Config>,
+    paths: Res<SearchPaths>,
     dimensions: Res<GridDimensions>,
     theme: Res<Theme>,
-    mut random: ResMut<Random>,
+    game: Res<GameState>,
+) {
+    commands.insert_resource(AudioAssets::new(
+        &asset_server,
+        &mut audio_sources,
+        &config,
+        &paths,
+    ));
+
+    let mut camera = OrthographicCameraBundle::new_2d();
+    // Keep the grid clear of the HUD's strip, which is part of the window but not of the grid
+    let strip = config
+        .hud
+        .strip_height(config.grid_scale, hud_lines(game.snakes.len())) as f32;
+    camera.transform.translation.y += match config.hud.edge {
+        HudEdge::Top => strip / 2.0,
+        HudEdge::Bottom => -strip / 2.0,
+    };
+    commands.spawn_bundle(camera);
+    commands.spawn_bundle(UiCameraBundle::default());
+    for position in game.grid.walls() {
+        wall_spawn(&mut commands, position, &dimensions, &theme);
+    }
+}
+
+fn hud_setup(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    config: NonSend<Config>,
+    theme: Res<Theme>,
+    game: Res<GameState>,
+) {
+    if config.hud.placement == HudPlacement::Hidden {
+        return;
+    }
+    let font_size = config.hud.font_size(config.grid_scale);
+    let height = config
+        .hud
+        .height(config.grid_scale, hud_lines(game.snakes.len()));
+    let position = match config.hud.edge {
+        HudEdge::Top => Rect {
+            top: Val::Px(0.0),
+            ..Default::default()
+        },
+        HudEdge::Bottom => Rect {
+            bottom: Val::Px(0.0),
+            ..Default::default()
+        },
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(height)),
+                position_type: PositionType::Absolute,
+                position,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(ThemeColor::HudBackground.get(&theme)),
+            ..Default::default()
+        })
+        .insert(ThemeColor::HudBackground)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size,
+                            color: ThemeColor::HudText.get(&theme),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(HudText)
+                .insert(ThemeColor::HudText);
+        });
+}
+
+fn hud_updating(
+    mut game_events: EventReader<GameEvent>,
+    mut stats: ResMut<SessionStats>,
+    mut texts: Query<&mut Text, With<HudText>>,
+    mut windows: ResMut<Windows>,
+    tick_timer: Res<TickTimer>,
+    game: Res<GameState>,
 ) {
-    commands.insert_resource(AudioAssets::new(&asset_server, &config));
-
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    let mut wall = |x, y| {
-        wall_spawn(
-            &mut commands,
-            &mut materials,
-            GridPosition::new(x, y),
-            &dimensions,
-            &theme,
+    let SessionStats {
+        best_scores,
+        deaths,
+    } = &mut *stats;
+    best_scores.resize(game.snakes.len(), 0);
+    deaths.resize(game.snakes.len(), 0);
+    for event in game_events.iter() {
+        if let GameEvent::SnakeDied { snake, .. } = event {
+            deaths[*snake] += 1;
+        }
+    }
+    for (best_score, snake) in best_scores.iter_mut().zip(game.snakes.iter()) {
+        *best_score = (*best_score).max(snake.score);
+    }
+
+    let value = hud_text(&game, &stats, tick_timer.tick_length);
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+    // The title is kept up to date as well, for when the HUD is hidden
+    let title = score_title(&game);
+    if let Some(window) = windows.get_primary_mut() {
+        if window.title() != title {
+            window.set_title(title);
+        }
+    }
+}
+
+/// The HUD has a line per snake, plus one for the speed when there is more than one snake.
+fn hud_lines(snakes: usize) -> usize {
+    match snakes {
+        0 | 1 => 1,
+        snakes => snakes + 1,
+    }
+}
+
+fn hud_text(game: &GameState, stats: &SessionStats, tick_length: f64) -> String {
+    let speed = format!("Speed {:.1}/s", 1.0 / tick_length);
+    let stats = |i: usize| {
+        format!(
+            "Score {}   Length {}   Best {}   Deaths {}",
+            game.snakes[i].score,
+            game.snakes[i].body.len(),
+            stats.best_scores[i],
+            stats.deaths[i]
         )
     };
+    match game.snakes.len() {
+        0 => speed,
+        1 => format!("{}   {}", stats(0), speed),
+        snakes => {
+            let mut lines: Vec<String> = (0..snakes)
+                .map(|i| format!("P{}   {}", i + 1, stats(i)))
+                .collect();
+            lines.push(speed);
+            lines.join("\n")
+        }
+    }
+}
 
-    let mut spawn_positions = SpawnPositions::default();
-    let mut spawn = |x, y, direction| {
-        spawn_positions
-            .spawn_positions
-            .push(SpawnPosition::new(GridPosition::new(x, y), direction));
+fn overlay_setup(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    config: NonSend<Config>,
+    theme: Res<Theme>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: UiColor(ThemeColor::Overlay.get(&theme)),
+            ..Default::default()
+        })
+        .insert(Overlay)
+        .insert(ThemeColor::Overlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: config.grid_scale as f32 * 0.75,
+                            color: ThemeColor::HudText.get(&theme),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(Overlay)
+                .insert(OverlayText)
+                .insert(ThemeColor::HudText);
+        });
+}
+
+fn overlay_updating(
+    mut visibilities: Query<&mut Visibility, With<Overlay>>,
+    mut texts: Query<&mut Text, With<OverlayText>>,
+    state: Res<State<AppState>>,
+    replay_mode: Res<ReplayMode>,
+    tick_timer: Res<TickTimer>,
+    tracking: Option<Res<HighScoreTracking>>,
+    config: NonSend<Config>,
+    game: Res<GameState>,
+) {
+    let playback = matches!(*replay_mode, ReplayMode::Playback(_));
+    let places = tracking
+        .as_ref()
+        .map_or(&[][..], |tracking| &tracking.places);
+    let value = overlay_text(
+        state.current(),
+        &game,
+        &config,
+        playback,
+        tick_timer.elapsed,
+        places,
+    );
+    for mut visibility in visibilities.iter_mut() {
+        if visibility.is_visible != value.is_some() {
+            visibility.is_visible = value.is_some();
+        }
+    }
+    if let Some(value) = value {
+        for mut text in texts.iter_mut() {
+            if text.sections[0].value != value {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+}
+
+/// What the overlay says on the title, pause and game over screens, or `None` while playing.
+fn overlay_text(
+    state: &AppState,
+    game: &GameState,
+    config: &Config,
+    playback: bool,
+    elapsed: f64,
+    places: &[Option<usize>],
+) -> Option<String> {
+    // A replay always plays out on the map it was recorded on
+    let new_map = if playback { "" } else { "   N for a new map" };
+    match state {
+        AppState::Playing => None,
+        AppState::Title => Some(format!(
+            "{}\n\nEnter to {}   Esc to quit",
+            TITLE,
+            if playback { "watch the replay" } else { "play" }
+        )),
+        AppState::Paused => Some(format!("Paused\n\nEsc to resume   R to restart{}", new_map)),
+        AppState::GameOver => {
+            let players = match config.players.len() {
+                0 => game.snakes.len(),
+                players => players,
+            };
+            let mut lines = vec!["Game over".to_string(), String::new()];
+            for (i, snake) in game.snakes.iter().take(players).enumerate() {
+                let mut line = match players {
+                    1 => format!("Score {}", snake.score),
+                    _ => format!("P{}   Score {}", i + 1, snake.score),
+                };
+                if let Some(place) = places.get(i).copied().flatten() {
+                    line += &format!("   #{} on the high scores", place);
+                }
+                lines.push(line);
+            }
+            let seconds = elapsed as u64;
+            lines.push(String::new());
+            lines.push(format!(
+                "Time {}:{:02}   Seed {}",
+                seconds / 60,
+                seconds % 60,
+                config.seed
+            ));
+            lines.push(String::new());
+            lines.push(format!("R to play again{}   Esc to quit", new_map));
+            Some(lines.join("\n"))
+        }
+    }
+}
+
+/// A key or button that controls the game outside of steering the snakes.
+#[derive(Clone, Copy, PartialEq)]
+enum MenuButton {
+    Key(KeyCode),
+    /// The Start button of any gamepad, which starts, pauses, resumes and restarts the game.
+    Start,
+}
+
+fn menu_input(
+    mut keyboard_input_reader: EventReader<KeyboardInput>,
+    mut gamepad_event_reader: EventReader<GamepadEvent>,
+    mut restarts: EventWriter<Restart>,
+    mut exit_events: EventWriter<AppExit>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let keys = keyboard_input_reader
+        .iter()
+        .filter(|event| event.state == ElementState::Pressed)
+        .filter_map(|event| event.key_code.map(MenuButton::Key));
+    let buttons = gamepad_event_reader
+        .iter()
+        .filter(|GamepadEvent(_, event_type)| match event_type {
+            GamepadEventType::ButtonChanged(GamepadButtonType::Start, value) => {
+                *value >= BUTTON_PRESS_THRESHOLD
+            }
+            _ => false,
+        })
+        .map(|_| MenuButton::Start);
+    let pressed: Vec<MenuButton> = keys.chain(buttons).collect();
+    for button in pressed {
+        use MenuButton::{Key, Start};
+        let next = match (state.current(), button) {
+            (
+                AppState::Title,
+                Key(KeyCode::Return | KeyCode::NumpadEnter | KeyCode::Space) | Start,
+            ) => AppState::Playing,
+            (AppState::Playing, Key(KeyCode::Escape | KeyCode::P) | Start) => AppState::Paused,
+            (AppState::Paused, Key(KeyCode::Escape | KeyCode::P) | Start) => AppState::Playing,
+            (AppState::Paused | AppState::GameOver, Key(KeyCode::R | KeyCode::N))
+            | (AppState::GameOver, Start) => {
+                restarts.send(Restart {
+                    new_seed: button == Key(KeyCode::N),
+                });
+                AppState::Playing
+            }
+            (AppState::Title | AppState::GameOver, Key(KeyCode::Escape)) => {
+                exit_events.send(AppExit);
+                return;
+            }
+            _ => continue,
+        };
+        // Only one change of state can be queued at a time, so the rest waits for the next frame
+        if let Err(error) = state.set(next) {
+            eprintln!("Failed to change state: {:?}", error);
+        }
+        return;
+    }
+}
+
+/// Starts the game over, keeping the session's stats and the configuration as it currently is.
+fn restarting(
+    mut commands: Commands,
+    mut restarts: EventReader<Restart>,
+    mut config: NonSendMut<Config>,
+    mut game: ResMut<GameState>,
+    mut sprites: ResMut<SnakeSprites>,
+    mut pending_inputs: ResMut<PendingInputs>,
+    mut tick_timer: ResMut<TickTimer>,
+    mut replay_mode: ResMut<ReplayMode>,
+    tracking: Option<ResMut<HighScoreTracking>>,
+    entities: Query<Entity, With<GridPosition>>,
+    dimensions: Res<GridDimensions>,
+    theme: Res<Theme>,
+) {
+    let new_seed = match restarts.iter().last() {
+        Some(restart) => restart.new_seed,
+        None => return,
+    };
+    // A replay being watched can only play out with the seed it was recorded with
+    let reseed = new_seed && !matches!(*replay_mode, ReplayMode::Playback(_));
+    let mut attempts = 0;
+    let (seed, map_data) = loop {
+        let seed = if reseed {
+            random::<u32>().into()
+        } else {
+            config.seed
+        };
+        let map_data = generate_map(&*config.map, seed);
+        let issues = validate_map(&map_data, config.snake_spawn_segments, config.wrap);
+        attempts += 1;
+        if !issues.iter().any(MapIssue::is_error) {
+            report_map_issues(&issues);
+            break (seed, map_data);
+        }
+        if !reseed || attempts == RESTART_ATTEMPTS {
+            report_map_issues(&issues);
+            eprintln!("No map that can be played was found, keeping the current game");
+            return;
+        }
     };
+    config.seed = seed;
+    // Runs cut short by restarting count like runs cut short by closing the game
+    if let Some(mut tracking) = tracking {
+        let ended = game
+            .snakes
+            .iter()
+            .enumerate()
+            .map(|(i, snake)| (i, snake.score, snake.body.len()));
+        tracking.end_runs(ended, &game, tick_timer.elapsed);
+        tracking.places.clear();
+        tracking.seed = config.seed;
+    }
+    if let ReplayMode::Recording { replay, .. } = &mut *replay_mode {
+        replay.restart(config.seed, &map_data);
+    }
+    // Maps are sized by their settings rather than their seed, so the grid keeps its dimensions
+    *game = GameState::new(&config, &map_data);
+    for entity in entities.iter() {
+        commands.entity(entity).despawn();
+    }
+    for position in game.grid.walls() {
+        wall_spawn(&mut commands, position, &dimensions, &theme);
+    }
+    sprites.snakes.clear();
+    pending_inputs.inputs.clear();
+    tick_timer.accumulator = 0.0;
+    tick_timer.elapsed = 0.0;
+}
+
+fn game_over_checking(mut state: ResMut<State<AppState>>, game: Res<GameState>) {
+    if game.is_over() {
+        if let Err(error) = state.set(AppState::GameOver) {
+            eprintln!("Failed to change state: {:?}", error);
+        }
+    }
+}
+
+fn tick_timing(
+    mut looping: Local<bool>,
+    mut timer: ResMut<TickTimer>,
+    state: Res<State<AppState>>,
+    time: Res<Time>,
+) -> ShouldRun {
+    // Time only passes for the game while it's being played
+    if *state.current() != AppState::Playing {
+        *looping = false;
+        return ShouldRun::No;
+    }
+    // Like `FixedTimestep`, catch up on every tick that has passed since the last frame
+    if !*looping {
+        timer.accumulator += time.delta_seconds_f64();
+        timer.elapsed += time.delta_seconds_f64();
+    }
+    if timer.accumulator >= timer.tick_length {
+        timer.accumulator -= timer.tick_length;
+        *looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        *looping = false;
+        ShouldRun::No
+    }
+}
+
+fn game_tick(
+    mut game: ResMut<GameState>,
+    mut pending_inputs: ResMut<PendingInputs>,
+    mut replay_mode: ResMut<ReplayMode>,
+    mut events: EventWriter<GameEvent>,
+    config: NonSend<Config>,
+) {
+    let mut headings = HashMap::default();
+    let mut inputs: Vec<Input> = pending_inputs
+        .inputs
+        .drain(..)
+        .filter(|input| input.snake < game.snakes.len())
+        .map(|input| {
+            let heading = headings
+                .entry(input.snake)
+                .or_insert_with(|| game.snakes[input.snake].heading());
+            let direction = match input.turn {
+                Turn::Towards(direction) => direction,
+                Turn::Clockwise => heading.clockwise(),
+                Turn::CounterClockwise => heading.counter_clockwise(),
+            };
+            // Follows the rules turns are queued by, so that relative turns in a row add up
+            if direction != *heading && direction != heading.opposite() {
+                *heading = direction;
+            }
+            Input {
+                snake: input.snake,
+                direction,
+            }
+        })
+        .collect();
+    for (i, brain) in config.ai.iter().enumerate() {
+        let snake = config.players.len() + i;
+        if game.snakes[snake].is_alive() {
+            inputs.push(Input {
+                snake,
+                direction: brain.next_direction(&game, snake),
+            });
+        }
+    }
+    match &mut *replay_mode {
+        ReplayMode::Off => {}
+        ReplayMode::Recording { replay, .. } => replay.record(game.tick, &inputs),
+        // Player input is ignored while watching a replay
+        ReplayMode::Playback(replay) => inputs = replay.inputs_at(game.tick),
+    }
+    for event in game.step(&inputs) {
+        events.send(event);
+    }
+}
+
+fn replay_saving(
+    mut game_events: EventReader<GameEvent>,
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut exit_events: EventReader<AppExit>,
+    replay_mode: Res<ReplayMode>,
+) {
+    // Save whenever a snake dies as well as on exit,
+    // so a recording survives the game crashing or being killed
+    let died = game_events
+        .iter()
+        .filter(|event| matches!(event, GameEvent::SnakeDied { .. }))
+        .count()
+        > 0;
+    let exiting = close_events.iter().count() + exit_events.iter().count() > 0;
+    if let ReplayMode::Recording { path, replay } = &*replay_mode {
+        if died || exiting {
+            if let Err(error) = replay.save(path) {
+                eprintln!("{}", error);
+            }
+        }
+    }
+}
+
+fn hot_reloading(
+    mut commands: Commands,
+    mut hot_reload: ResMut<HotReload>,
+    mut config: NonSendMut<Config>,
+    mut theme: ResMut<Theme>,
+    mut game: ResMut<GameState>,
+    mut tick_timer: ResMut<TickTimer>,
+    mut audio_sources: ResMut<Assets<AudioSource>>,
+    asset_server: Res<AssetServer>,
+    paths: Res<SearchPaths>,
+    replay_mode: Res<ReplayMode>,
+    tracking: Option<ResMut<HighScoreTracking>>,
+    time: Res<Time>,
+) {
+    if !hot_reload.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let HotReload {
+        args,
+        config_watcher,
+        theme_watcher,
+        config_table,
+        ..
+    } = &mut *hot_reload;
+    let mut reload_theme = theme_watcher.as_mut().is_some_and(FileWatcher::changed);
 
-    let map_data = config.map.get_map_data(&mut random.environment_generator);
-    let top = map_data.height - 1;
-    for (x, y, cell) in map_data.iter() {
-        match cell {
-            Cell::Empty => {}
-            Cell::Wall => wall(x, top - y),
-            Cell::Spawn(direction) => spawn(x, top - y, direction),
+    if config_watcher.as_mut().is_some_and(FileWatcher::changed) {
+        match load_config(args, &paths, None) {
+            Err(error) => eprintln!("{}", error),
+            Ok(loaded) if !loaded.errors.is_empty() => {
+                report_config_errors(&loaded.errors, &loaded.warnings, false);
+                eprintln!("Keeping the previous configuration until the errors are fixed");
+            }
+            Ok(loaded) => {
+                report_config_errors(&loaded.errors, &loaded.warnings, false);
+                let changed: BTreeSet<&String> = config_table
+                    .keys()
+                    .chain(loaded.table.keys())
+                    .filter(|key| config_table.get(*key) != loaded.table.get(*key))
+                    .collect();
+                // Changing how the game plays out would make the replay being recorded play out differently
+                let recording = matches!(*replay_mode, ReplayMode::Recording { .. });
+                let (mut retimed, mut audio_changed) = (false, false);
+                let mut needs_restart = Vec::new();
+                for key in changed {
+                    match key.as_str() {
+                        "theme" => {
+                            config.theme = loaded.value.theme.clone();
+                            *theme_watcher = find_theme(&paths, &config.theme)
+                                .ok()
+                                .map(FileWatcher::new);
+                            reload_theme = true;
+                        }
+                        "eat_audio" | "destroy_audio" | "spawn_food_audio"
+                        | "spawn_snake_audio" => audio_changed = true,
+                        "tick_length"
+                        | "food_ticks"
+                        | "snake_spawn_segments"
+                        | "snake_segment_despawn_interval"
+                        | "snake_respawn_delay"
+                        | "respawn"
+                        | "input_queue_length"
+                            if !recording =>
+                        {
+                            retimed = true
+                        }
+                        _ => needs_restart.push(key.as_str()),
+                    }
+                }
+                let new = &loaded.value;
+                if audio_changed {
+                    config.eat_audio = new.eat_audio.clone();
+                    config.destroy_audio = new.destroy_audio.clone();
+                    config.spawn_food_audio = new.spawn_food_audio.clone();
+                    config.spawn_snake_audio = new.spawn_snake_audio.clone();
+                    commands.insert_resource(AudioAssets::new(
+                        &asset_server,
+                        &mut audio_sources,
+                        &config,
+                        &paths,
+                    ));
+                }
+                if retimed {
+                    config.tick_length = new.tick_length;
+                    config.food_ticks = new.food_ticks;
+                    config.snake_spawn_segments = new.snake_spawn_segments;
+                    config.snake_segment_despawn_interval = new.snake_segment_despawn_interval;
+                    config.snake_respawn_delay = new.snake_respawn_delay;
+                    config.respawn = new.respawn;
+                    config.input_queue_length = new.input_queue_length;
+                    game.reconfigure(&config);
+                    tick_timer.tick_length = config.tick_length;
+                }
+                // Runs so far were played under the old settings, so they end there
+                // rather than going into the table for the new ones
+                if let Some(mut tracking) = tracking {
+                    let settings = gameplay_settings(&config);
+                    if settings != tracking.settings {
+                        let ended = game
+                            .snakes
+                            .iter()
+                            .enumerate()
+                            .map(|(i, snake)| (i, snake.score, snake.body.len()));
+                        tracking.end_runs(ended, &game, tick_timer.elapsed);
+                        tracking.settings = settings;
+                    }
+                }
+                if !needs_restart.is_empty() {
+                    eprintln!(
+                        "Changes to {} take effect after restarting Hebi",
+                        needs_restart.join(", ")
+                    );
+                }
+                *config_table = loaded.table;
+            }
         }
     }
 
-    commands.insert_resource(spawn_positions);
+    if reload_theme {
+        let (_, loaded) = load_theme(&paths, &config.theme);
+        report_config_errors(&loaded.errors, &loaded.warnings, false);
+        if loaded.errors.is_empty() {
+            *theme = loaded.value;
+        } else {
+            eprintln!("Keeping the previous theme until the errors are fixed");
+        }
+    }
+}
+
+fn recoloring(
+    mut clear_color: ResMut<ClearColor>,
+    mut sprites: Query<(&ThemeColor, &mut Sprite), Without<Despawning>>,
+    mut texts: Query<(&ThemeColor, &mut Text)>,
+    mut nodes: Query<(&ThemeColor, &mut UiColor)>,
+    theme: Res<Theme>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    clear_color.0 = Color::hex(&theme.background).unwrap_or(MISSING_COLOR);
+    for (color, mut sprite) in sprites.iter_mut() {
+        sprite.color = color.get(&theme);
+    }
+    for (color, mut text) in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = color.get(&theme);
+        }
+    }
+    for (color, mut node) in nodes.iter_mut() {
+        node.0 = color.get(&theme);
+    }
+}
+
+fn high_score_tracking(
+    mut game_events: EventReader<GameEvent>,
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut exit_events: EventReader<AppExit>,
+    tracking: Option<ResMut<HighScoreTracking>>,
+    game: Res<GameState>,
+    tick_timer: Res<TickTimer>,
+) {
+    let mut tracking = match tracking {
+        Some(tracking) => tracking,
+        None => return,
+    };
+    let tracking = &mut *tracking;
+    let now = tick_timer.elapsed;
+    tracking.runs.resize(game.snakes.len(), None);
+
+    // Handled in order, since several ticks can pass in a frame and a snake can die and respawn in them
+    for event in game_events.iter() {
+        match event {
+            // Only players go on the high-score table
+            GameEvent::SnakeSpawned { snake } if *snake < tracking.players => {
+                tracking.runs[*snake] = Some((now, game.snakes[*snake].body.len()));
+            }
+            GameEvent::SnakeDied {
+                snake,
+                score,
+                length,
+            } => tracking.end_runs([(*snake, *score, *length)], &game, now),
+            _ => {}
+        }
+    }
+    for (run, snake) in tracking.runs.iter_mut().zip(game.snakes.iter()) {
+        if let Some((_, length)) = run {
+            *length = (*length).max(snake.body.len());
+        }
+    }
+    // Runs still going when the game is closed count too
+    if close_events.iter().count() + exit_events.iter().count() > 0 {
+        let ended = game
+            .snakes
+            .iter()
+            .enumerate()
+            .map(|(i, snake)| (i, snake.score, snake.body.len()));
+        tracking.end_runs(ended, &game, now);
+    }
 }
 
 fn grid_positioning(
@@ -213,10 +1430,15 @@ fn grid_positioning(
     dimensions: Res<GridDimensions>,
 ) {
     for (grid_position, mut transform) in query.iter_mut() {
-        transform.translation = transform.translation.lerp(
-            grid_to_vector(grid_position, &dimensions),
-            grid_position.t.unwrap_or(1.0),
-        );
+        let target = grid_to_vector(grid_position, &dimensions);
+        // Jump straight to positions more than a cell away, such as when wrapping around an edge,
+        // instead of sliding across the whole board
+        let t = if transform.translation.distance(target) > dimensions.scale as f32 * 1.5 {
+            1.0
+        } else {
+            grid_position.t.unwrap_or(1.0)
+        };
+        transform.translation = transform.translation.lerp(target, t);
     }
 }
 
@@ -230,35 +1452,64 @@ fn grid_to_vector(grid_position: &GridPosition, dimensions: &GridDimensions) ->
     )
 }
 
-fn food_spawn(
+fn food_rendering(
     mut commands: Commands,
-    _materials: ResMut<Assets<ColorMaterial>>,
-    grid_positions: Query<&GridPosition>,
+    mut game_events: EventReader<GameEvent>,
+    foods: Query<(Entity, &GridPosition), With<Food>>,
     audio: Res<Audio>,
     audio_assets: Res<AudioAssets>,
     dimensions: Res<GridDimensions>,
     theme: Res<Theme>,
-    mut random: ResMut<Random>,
+    time: Res<Time>,
 ) {
-    // Return and spawn no food if there are no available grid positions (entire grid full)
-    if grid_positions.iter().len() >= (dimensions.width * dimensions.height) as usize {
-        return;
-    }
-    // This will prevent an infinite loop here:
-    let grid_position = 'outer: loop {
-        let possible_grid_position = GridPosition::new(
-            random.snake_spawn_generator.next_u32() % dimensions.width,
-            random.snake_spawn_generator.next_u32() % dimensions.height,
-        );
-        for exisiting_grid_position in grid_positions.iter() {
-            if exisiting_grid_position.x == possible_grid_position.x
-                && exisiting_grid_position.y == possible_grid_position.y
-            {
-                continue 'outer;
+    for event in game_events.iter() {
+        match event {
+            GameEvent::FoodSpawned { position, variant } => {
+                food_spawn(&mut commands, *position, *variant, &dimensions, &theme);
+                audio.play(audio_assets.spawn_food.clone_weak());
+            }
+            GameEvent::FoodEaten { position } => {
+                for (food, food_position) in foods.iter() {
+                    if food_position.x == position.x && food_position.y == position.y {
+                        commands
+                            .entity(food)
+                            .remove::<Food>()
+                            .insert(Despawning::new(
+                                time.seconds_since_startup(),
+                                0.0,
+                                Some(audio_assets.eat.clone_weak()),
+                            ));
+                    }
+                }
             }
+            _ => {}
         }
-        break possible_grid_position;
-    };
+    }
+}
+
+fn score_title(game: &GameState) -> String {
+    match game.snakes.as_slice() {
+        [snake] => format!("{} — Score: {}", TITLE, snake.score),
+        snakes => {
+            let scores: Vec<String> = snakes
+                .iter()
+                .enumerate()
+                .map(|(i, snake)| format!("P{}: {}", i + 1, snake.score))
+                .collect();
+            format!("{} — {}", TITLE, scores.join(" | "))
+        }
+    }
+}
+
+fn food_spawn(
+    commands: &mut Commands,
+    position: Position,
+    variant: u32,
+    dimensions: &GridDimensions,
+    theme: &Theme,
+) {
+    let grid_position = GridPosition::new(position.x, position.y);
+    let color = ThemeColor::Food(variant);
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -266,25 +1517,24 @@ fn food_spawn(
                     dimensions.scale as f32 * 0.875,
                     dimensions.scale as f32 * 0.875,
                 )),
-                color: Color::hex(theme.food.choose(&mut random.food_spawn_generator).unwrap())
-                    .unwrap_or(MISSING_COLOR),
+                color: color.get(theme),
                 ..Default::default()
             },
-            transform: Transform::from_translation(grid_to_vector(&grid_position, &dimensions)),
+            transform: Transform::from_translation(grid_to_vector(&grid_position, dimensions)),
             ..Default::default()
         })
         .insert(grid_position)
+        .insert(color)
         .insert(Food);
-    audio.play(audio_assets.spawn_food.clone_weak());
 }
 
 fn wall_spawn(
     commands: &mut Commands,
-    _materials: &mut Assets<ColorMaterial>,
-    grid_position: GridPosition,
+    position: Position,
     dimensions: &GridDimensions,
     theme: &Theme,
 ) {
+    let grid_position = GridPosition::new(position.x, position.y);
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -292,226 +1542,173 @@ fn wall_spawn(
                     dimensions.scale as f32,
                     dimensions.scale as f32
                 )),
-                color: Color::hex(&theme.walls).unwrap_or(MISSING_COLOR),
+                color: ThemeColor::Walls.get(theme),
                 ..Default::default()
             },
             transform: Transform::from_translation(grid_to_vector(&grid_position, dimensions)),
             ..Default::default()
         })
         .insert(grid_position)
-        .insert(Collidable);
+        .insert(ThemeColor::Walls);
 }
 
-fn snake_respawn(
-    mut respawn: ResMut<Respawn>,
-    mut respawn_writer: EventWriter<RespawnEvent>,
-    time: Res<Time>,
-) {
-    if respawn.time <= time.seconds_since_startup() && !respawn.completed {
-        respawn_writer.send(RespawnEvent);
-        respawn.completed = true;
-    }
-}
-
-fn snake_spawn(
+fn snake_rendering(
     mut commands: Commands,
-    mut spawn_reader: EventReader<RespawnEvent>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut windows: ResMut<Windows>,
-    spawn_positions: Res<SpawnPositions>,
+    mut game_events: EventReader<GameEvent>,
+    mut sprites: ResMut<SnakeSprites>,
+    mut grid_positions: Query<&mut GridPosition, With<SnakeSegment>>,
     audio: Res<Audio>,
     audio_assets: Res<AudioAssets>,
     config: NonSend<Config>,
     dimensions: Res<GridDimensions>,
     theme: Res<Theme>,
-    mut random: ResMut<Random>,
+    time: Res<Time>,
+    game: Res<GameState>,
 ) {
-    for _respawn_event in spawn_reader.iter() {
-        let spawn_position = spawn_positions
-            .spawn_positions
-            .choose(&mut random.snake_spawn_generator)
-            .unwrap();
-        let mut snake_head = SnakeHead::new(spawn_position.direction);
-        let snake_head_position = spawn_position.grid_position.clone();
-        let segment_direction = snake_head.direction.opposite().vec();
-        for i in 1..config.snake_spawn_segments {
-            snake_head.spawn_segment(
-                None,
-                &mut commands,
-                &mut materials,
-                GridPosition::new(
-                    ((segment_direction.x * (i as f32)) + snake_head_position.x as f32) as u32,
-                    ((segment_direction.y * (i as f32)) + snake_head_position.y as f32) as u32,
-                ),
-                &mut windows,
-                &config,
-                &dimensions,
-                &theme,
-            )
-        }
-        commands
-            .spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(
-                        config.grid_scale as f32 * 0.875,
-                        config.grid_scale as f32 * 0.875,
-                    )),
-                    color: Color::hex(&theme.snake).unwrap_or(MISSING_COLOR),
-                    ..Default::default()
-                },
-                transform: Transform::from_translation(grid_to_vector(
-                    &snake_head_position,
-                    &dimensions,
+    let SnakeSprites { snakes } = &mut *sprites;
+    snakes.resize_with(game.snakes.len(), Vec::new);
+    for event in game_events.iter() {
+        match event {
+            GameEvent::SnakeSpawned { snake } => {
+                let segments = &mut snakes[*snake];
+                for position in game.snakes[*snake].body.iter() {
+                    let is_head = segments.is_empty();
+                    segments.push(segment_spawn(
+                        &mut commands,
+                        *position,
+                        is_head,
+                        *snake,
+                        &theme,
+                        &dimensions,
+                    ));
+                }
+                audio.play(audio_assets.spawn_snake.clone_weak());
+            }
+            GameEvent::SnakeMoved { snake } => {
+                let segments = &mut snakes[*snake];
+                for (i, position) in game.snakes[*snake].body.iter().enumerate() {
+                    match segments.get(i) {
+                        Some(segment) => {
+                            if let Ok(mut grid_position) = grid_positions.get_mut(*segment) {
+                                grid_position.x = position.x;
+                                grid_position.y = position.y;
+                            }
+                        }
+                        // The snake grew, so its new tail stays where the old tail was
+                        None => segments.push(segment_spawn(
+                            &mut commands,
+                            *position,
+                            false,
+                            *snake,
+                            &theme,
+                            &dimensions,
+                        )),
+                    }
+                }
+            }
+            GameEvent::SnakeDied { snake, .. } => {
+                for (i, segment) in snakes[*snake].drain(..).enumerate() {
+                    commands
+                        .entity(segment)
+                        .remove::<SnakeSegment>()
+                        .insert(Despawning::new(
+                            time.seconds_since_startup(),
+                            i as f64 * config.snake_segment_despawn_interval,
+                            Some(audio_assets.destroy.clone_weak()),
+                        ));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn segment_spawn(
+    commands: &mut Commands,
+    position: Position,
+    is_head: bool,
+    snake: usize,
+    theme: &Theme,
+    dimensions: &GridDimensions,
+) -> Entity {
+    let grid_position = GridPosition::new(position.x, position.y);
+    let size = if is_head { 0.875 } else { 0.75 };
+    let color = ThemeColor::Snake(snake);
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(
+                    dimensions.scale as f32 * size,
+                    dimensions.scale as f32 * size,
                 )),
+                color: color.get(theme),
                 ..Default::default()
-            })
-            .insert(snake_head_position)
-            .insert(snake_head);
-        audio.play(audio_assets.spawn_snake.clone_weak());
-    }
+            },
+            transform: Transform::from_translation(grid_to_vector(&grid_position, dimensions)),
+            ..Default::default()
+        })
+        .insert(SnakeSegment)
+        .insert(grid_position)
+        .insert(color)
+        .id()
 }
 
 fn snake_movement_input(
     mut keyboard_input_reader: EventReader<KeyboardInput>,
-    mut snake_heads: Query<&mut SnakeHead>,
+    mut gamepad_event_reader: EventReader<GamepadEvent>,
+    mut gamepads: Local<GamepadStates>,
+    mut pending_inputs: ResMut<PendingInputs>,
     controls: Res<DirectionalControls>,
+    state: Res<State<AppState>>,
 ) {
-    let mut direction = None;
-
+    // Runs in every state so that gamepads are always tracked, otherwise a button let go of
+    // while paused would still count as held after resuming. Only inputs made while playing count.
+    let playing = *state.current() == AppState::Playing;
     for event in keyboard_input_reader.iter() {
-        if event.state == ElementState::Released {
+        if event.state == ElementState::Released || !playing {
             continue;
         }
 
-        direction = controls.from_keyboard(event).or(direction);
+        pending_inputs
+            .inputs
+            .extend_from_slice(controls.from_keyboard(event));
     }
 
-    if let Some(direction) = direction {
-        for mut snake_head in snake_heads.iter_mut() {
-            if direction == snake_head.direction.opposite() {
-                continue;
-            }
-
-            snake_head.next_direction = direction;
-        }
-    }
-}
-
-fn snake_movement(
-    mut snake_heads: Query<(&mut SnakeHead, &mut GridPosition)>,
-    mut grid_positions: Query<&mut GridPosition, Without<SnakeHead>>,
-) {
-    for (mut snake_head, mut grid_position) in snake_heads.iter_mut() {
-        snake_head.direction = snake_head.next_direction;
-        let direction_vector = snake_head.direction.vec();
-        snake_head.update_segment_positions(&grid_position, &mut grid_positions);
-        grid_position.x = (grid_position.x as f32 + direction_vector.x) as u32;
-        grid_position.y = (grid_position.y as f32 + direction_vector.y) as u32;
-    }
-}
-
-#[derive(Component)]
-struct Collidable;
-
-fn snake_collision_check(
-    mut commands: Commands,
-    mut snake_heads: Query<(Entity, &SnakeHead, &GridPosition)>,
-    grid_positions: Query<&GridPosition, With<Collidable>>,
-    time: Res<Time>,
-    mut respawn_event: ResMut<Respawn>,
-    audio_assets: Res<AudioAssets>,
-    config: NonSend<Config>,
-    dimensions: Res<GridDimensions>,
-) {
-    for (snake_head_entity, snake_head, snake_head_position) in snake_heads.iter_mut() {
-        let mut despawn = || {
-            snake_head.despawn(
-                &mut commands,
-                snake_head_entity,
-                &time,
-                &mut respawn_event,
-                &audio_assets,
-                &config,
-            );
-        };
-        // It is unnecessary to check if the x- or y-positions are less than 0
-        // since this is impossible for the unsigned integers that they are stored in
-        if snake_head_position.x >= dimensions.width || snake_head_position.y >= dimensions.height {
-            despawn();
-        }
-        for segment in snake_head.segments.iter() {
-            let segment_position = match grid_positions.get(*segment) {
-                Ok(position) => position,
-                Err(_) => continue,
-            };
-            if snake_head_position.x == segment_position.x
-                && snake_head_position.y == segment_position.y
-            {
-                despawn();
-            }
-        }
-        for grid_position in grid_positions.iter() {
-            if snake_head_position.x == grid_position.x && snake_head_position.y == grid_position.y
-            {
-                despawn();
+    for GamepadEvent(gamepad, event_type) in gamepad_event_reader.iter() {
+        match event_type {
+            GamepadEventType::ButtonChanged(button, value) => {
+                let key = (gamepad.0, *button);
+                if *value < BUTTON_PRESS_THRESHOLD {
+                    gamepads.pressed_buttons.remove(&key);
+                } else if gamepads.pressed_buttons.insert(key) && playing {
+                    pending_inputs
+                        .inputs
+                        .extend(controls.from_gamepad_button(*gamepad, *button));
+                }
             }
-        }
-    }
-}
-
-fn snake_eating(
-    mut commands: Commands,
-    mut snake_heads: Query<(&mut SnakeHead, &GridPosition)>,
-    foods: Query<(Entity, &GridPosition), With<Food>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    time: Res<Time>,
-    mut windows: ResMut<Windows>,
-    audio_assets: Res<AudioAssets>,
-    config: NonSend<Config>,
-    dimensions: Res<GridDimensions>,
-    theme: Res<Theme>,
-) {
-    for (mut snake_head, snake_head_grid_position) in snake_heads.iter_mut() {
-        for (food, food_position) in foods.iter() {
-            if food_position.x == snake_head_grid_position.x
-                && food_position.y == snake_head_grid_position.y
-            {
-                commands
-                    .entity(food)
-                    .remove::<Food>()
-                    .insert(Despawning::new(
-                        time.seconds_since_startup(),
-                        0.0,
-                        Some(audio_assets.eat.clone_weak()),
-                    ));
-                snake_head.spawn_segment(
-                    Some(0),
-                    &mut commands,
-                    &mut materials,
-                    snake_head_grid_position.clone(),
-                    &mut windows,
-                    &config,
-                    &dimensions,
-                    &theme,
-                );
+            GamepadEventType::AxisChanged(axis, value) => {
+                let previous = gamepads
+                    .axes
+                    .insert((gamepad.0, *axis), *value)
+                    .unwrap_or(0.0);
+                if playing {
+                    pending_inputs
+                        .inputs
+                        .extend(controls.from_gamepad_axis(*gamepad, *axis, previous, *value));
+                }
             }
+            _ => {}
         }
     }
 }
 
 fn despawning(
     mut commands: Commands,
-    mut despawning_objects: Query<(
-        Entity,
-        &mut Despawning,
-        &mut Transform,
-        &Handle<ColorMaterial>,
-    )>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut despawning_objects: Query<(Entity, &mut Despawning, &mut Transform, &mut Sprite)>,
     time: Res<Time>,
     audio: Res<Audio>,
 ) {
-    for (entity, mut despawning, mut transform, material_handle) in despawning_objects.iter_mut() {
+    for (entity, mut despawning, mut transform, mut sprite) in despawning_objects.iter_mut() {
         if time.seconds_since_startup() - despawning.despawn_time < despawning.animation_delay {
             continue;
         }
@@ -524,9 +1721,8 @@ fn despawning(
             }
         }
         transform.scale *= 1.125;
-        let material = materials.get_mut(material_handle).unwrap();
-        let alpha = material.color.a() / 1.5;
-        material.color.set_a(alpha);
+        let alpha = sprite.color.a() / 1.5;
+        sprite.color.set_a(alpha);
         // Only despawn if alpha value is 0 when converted to an 8-bit color value
         // One can't check if alpha == 0.0 since this will never happen,
         // and using an arbitrary small value (if alpha < 0.01) isn't precise.
@@ -537,161 +1733,6 @@ fn despawning(
     }
 }
 
-#[derive(Default)]
-struct Respawn {
-    time: f64,
-    completed: bool,
-}
-
-struct RespawnEvent;
-
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub enum Direction {
-    Left,
-    Right,
-    Down,
-    Up,
-}
-
-impl Direction {
-    fn opposite(&self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-            Self::Down => Self::Up,
-            Self::Up => Self::Down,
-        }
-    }
-    fn vec(&self) -> Vec2 {
-        match self {
-            Self::Left => Vec2::new(-1.0, 0.0),
-            Self::Right => Vec2::new(1.0, 0.0),
-            Self::Down => Vec2::new(0.0, -1.0),
-            Self::Up => Vec2::new(0.0, 1.0),
-        }
-    }
-}
-
-#[derive(Component)]
-struct SnakeHead {
-    direction: Direction,
-    next_direction: Direction,
-    segments: Vec<Entity>,
-}
-
-impl SnakeHead {
-    fn new(direction: Direction) -> Self {
-        SnakeHead {
-            direction,
-            next_direction: direction,
-            segments: Vec::new(),
-        }
-    }
-    fn spawn_segment(
-        &mut self,
-        index: Option<usize>,
-        commands: &mut Commands,
-        _materials: &mut Assets<ColorMaterial>,
-        grid_position: GridPosition,
-        windows: &mut Windows,
-        config: &Config,
-        dimensions: &GridDimensions,
-        theme: &Theme,
-    ) {
-        self.segments.insert(
-            match index {
-                Some(index) => index,
-                None => self.segments.len(),
-            },
-            commands
-                .spawn_bundle(SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(
-                            config.grid_scale as f32 * 0.75,
-                            config.grid_scale as f32 * 0.75,
-                        )),
-                        color: Color::hex(&theme.snake).unwrap_or(MISSING_COLOR),
-                        ..Default::default()
-                    },
-                    transform: Transform::from_translation(grid_to_vector(
-                        &grid_position,
-                        dimensions,
-                    )),
-                    ..Default::default()
-                })
-                .insert(SnakeSegment)
-                .insert(grid_position)
-                .insert(Collidable)
-                .id(),
-        );
-        windows.get_primary_mut().unwrap().set_title(format!(
-            "{} — Score: {}",
-            TITLE,
-            self.segments.len() as u32 + 1 - config.snake_spawn_segments
-        ));
-    }
-    fn update_segment_positions(
-        &mut self,
-        head_position: &GridPosition,
-        grid_positions: &mut Query<&mut GridPosition, Without<SnakeHead>>,
-    ) {
-        let mut new_segment_positions = Vec::<GridPosition>::new();
-        for (i, _segment_position) in self.segments.iter().enumerate() {
-            if i == 0 {
-                new_segment_positions.push(head_position.clone());
-                continue;
-            }
-            new_segment_positions.push(
-                (grid_positions
-                    .get_mut(*self.segments.get(i - 1).unwrap())
-                    .unwrap())
-                .clone(),
-            );
-        }
-        for (i, new_segment_position) in new_segment_positions.iter().enumerate() {
-            let mut segment_position = match grid_positions.get_mut(*self.segments.get(i).unwrap())
-            {
-                Ok(position) => position,
-                Err(_) => continue,
-            };
-            segment_position.x = new_segment_position.x;
-            segment_position.y = new_segment_position.y;
-        }
-    }
-    fn despawn(
-        &self,
-        commands: &mut Commands,
-        entity: Entity,
-        time: &Time,
-        respawn_event: &mut Respawn,
-        audio_assets: &AudioAssets,
-        config: &Config,
-    ) {
-        for (i, segment) in self.segments.iter().enumerate() {
-            commands
-                .entity(*segment)
-                .remove::<SnakeSegment>()
-                .insert(Despawning::new(
-                    time.seconds_since_startup(),
-                    (i + 1) as f64 * config.snake_segment_despawn_interval,
-                    Some(audio_assets.destroy.clone_weak()),
-                ));
-        }
-        commands
-            .entity(entity)
-            .remove::<SnakeHead>()
-            .insert(Despawning::new(
-                time.seconds_since_startup(),
-                0.0,
-                Some(audio_assets.destroy.clone_weak()),
-            ));
-        respawn_event.time = time.seconds_since_startup()
-            + config.snake_segment_despawn_interval * self.segments.len() as f64
-            + config.snake_respawn_delay;
-        respawn_event.completed = false;
-    }
-}
-
 #[derive(Component)]
 struct SnakeSegment;
 
@@ -717,6 +1758,53 @@ impl Despawning {
 #[derive(Component)]
 struct Food;
 
+#[derive(Component)]
+struct HudText;
+
+/// The title, pause and game over screens, which cover the board while the game isn't being played.
+#[derive(Component)]
+struct Overlay;
+
+#[derive(Component)]
+struct OverlayText;
+
+/// Which theme color a sprite is drawn in, so it can be recolored when the theme changes.
+#[derive(Component, Clone, Copy)]
+enum ThemeColor {
+    Walls,
+    Snake(usize),
+    Food(u32),
+    HudText,
+    HudBackground,
+    /// The background, made translucent so that the board shows through.
+    Overlay,
+}
+
+impl ThemeColor {
+    fn get(&self, theme: &Theme) -> Color {
+        let hex = match self {
+            Self::Walls => Some(theme.walls.as_str()),
+            Self::Snake(snake) => Some(theme.snake_color(*snake)),
+            Self::Food(variant) => match theme.food.len() {
+                0 => None,
+                colors => theme.food.get(*variant as usize % colors).map(String::as_str),
+            },
+            Self::HudText => Some(theme.hud_text.as_deref().unwrap_or(&theme.snake)),
+            Self::HudBackground => match &theme.hud_background {
+                Some(color) => Some(color.as_str()),
+                None => return Color::NONE,
+            },
+            Self::Overlay => {
+                let mut color = Color::hex(&theme.background).unwrap_or(MISSING_COLOR);
+                color.set_a(0.75);
+                return color;
+            }
+        };
+        hex.and_then(|hex| Color::hex(hex).ok())
+            .unwrap_or(MISSING_COLOR)
+    }
+}
+
 #[derive(Default, Clone)]
 #[derive(Component)]
 struct GridPosition {
@@ -735,25 +1823,6 @@ impl GridPosition {
     }
 }
 
-struct SpawnPosition {
-    grid_position: GridPosition,
-    direction: Direction,
-}
-
-impl SpawnPosition {
-    fn new(grid_position: GridPosition, direction: Direction) -> Self {
-        SpawnPosition {
-            grid_position,
-            direction,
-        }
-    }
-}
-
-#[derive(Default)]
-struct SpawnPositions {
-    spawn_positions: Vec<SpawnPosition>,
-}
-
 struct AudioAssets {
     destroy: Handle<AudioSource>,
     eat: Handle<AudioSource>,
@@ -762,8 +1831,20 @@ struct AudioAssets {
 }
 
 impl AudioAssets {
-    fn new(asset_server: &AssetServer, config: &Config) -> Self {
-        let load = |name: &str| asset_server.load(format!("sounds/{}", name).as_str());
+    fn new(
+        asset_server: &AssetServer,
+        audio_sources: &mut Assets<AudioSource>,
+        config: &Config,
+        paths: &SearchPaths,
+    ) -> Self {
+        // Sounds that can't be found at all are still passed on to Bevy, which reports them missing
+        let mut load = |name: &str| match (paths.sound(name), embedded::sound(name)) {
+            (Some(path), _) => asset_server.load(path),
+            (None, Some(bytes)) => audio_sources.add(AudioSource {
+                bytes: bytes.into(),
+            }),
+            (None, None) => asset_server.load(format!("sounds/{}", name).as_str()),
+        };
         AudioAssets {
             destroy: load(&config.destroy_audio),
             eat: load(&config.eat_audio),
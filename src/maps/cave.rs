@@ -0,0 +1,240 @@
+use crate::{
+    config::{Cell, Map, MapData},
+    game::Direction,
+};
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How many times to regenerate a cave that ends up with too little open area before settling for
+/// the most open one so far.
+const MAX_ATTEMPTS: u32 = 20;
+
+/// How far apart spawns are kept when there's room for it, so snakes don't start on top of each other.
+const SPAWN_SPACING: u32 = 4;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct CaveMap {
+    pub width: u32,
+    pub height: u32,
+    fill_probability: f32,
+    iterations: u32,
+    birth_threshold: u32,
+    survival_threshold: u32,
+    /// Percentage of the map inside the border, from 0 to 100.
+    min_open_area: u32,
+    spawns: u32,
+}
+
+impl Default for CaveMap {
+    fn default() -> Self {
+        Self {
+            width: 34,
+            height: 22,
+            fill_probability: 0.45,
+            iterations: 4,
+            birth_threshold: 5,
+            survival_threshold: 4,
+            min_open_area: 40,
+            spawns: 4,
+        }
+    }
+}
+
+/// A grid of walls, with everything outside of it counting as wall.
+struct Cave {
+    width: u32,
+    height: u32,
+    walls: Vec<bool>,
+}
+
+impl Cave {
+    fn is_wall(&self, x: i64, y: i64) -> bool {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return true;
+        }
+        self.walls[(y * self.width as i64 + x) as usize]
+    }
+
+    fn is_border(&self, x: u32, y: u32) -> bool {
+        x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1
+    }
+
+    fn positions(&self) -> impl Iterator<Item = (u32, u32)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    fn open_count(&self) -> usize {
+        self.walls.iter().filter(|wall| !**wall).count()
+    }
+
+    fn random(width: u32, height: u32, fill_probability: f32, generator: &mut Pcg64) -> Self {
+        let mut cave = Self {
+            width,
+            height,
+            walls: Vec::with_capacity((width * height) as usize),
+        };
+        for (x, y) in cave.positions().collect::<Vec<_>>() {
+            let wall = cave.is_border(x, y) || generator.gen::<f32>() < fill_probability;
+            cave.walls.push(wall);
+        }
+        cave
+    }
+
+    /// Runs one pass of the automaton. Each cell counts the walls among its eight neighbours:
+    /// an open cell becomes a wall with at least `birth_threshold` of them,
+    /// and a wall stays a wall with at least `survival_threshold`.
+    fn smooth(&mut self, birth_threshold: u32, survival_threshold: u32) {
+        let walls = self
+            .positions()
+            .map(|(x, y)| {
+                if self.is_border(x, y) {
+                    return true;
+                }
+                let (x, y) = (x as i64, y as i64);
+                let neighbours = (-1..=1)
+                    .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                    .filter(|&(dx, dy)| (dx, dy) != (0, 0) && self.is_wall(x + dx, y + dy))
+                    .count() as u32;
+                if self.is_wall(x, y) {
+                    neighbours >= survival_threshold
+                } else {
+                    neighbours >= birth_threshold
+                }
+            })
+            .collect();
+        self.walls = walls;
+    }
+
+    /// Fills in every open region except the largest one, so that the whole cave is reachable.
+    fn keep_largest_region(&mut self) {
+        let mut region = vec![None; self.walls.len()];
+        let mut sizes = Vec::new();
+        for (x, y) in self.positions().collect::<Vec<_>>() {
+            let index = (y * self.width + x) as usize;
+            if self.walls[index] || region[index].is_some() {
+                continue;
+            }
+            let id = sizes.len();
+            let mut size = 0;
+            let mut stack = vec![(x, y)];
+            region[index] = Some(id);
+            while let Some((x, y)) = stack.pop() {
+                size += 1;
+                for direction in Direction::ALL {
+                    let (dx, dy) = MapData::offset(direction);
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if self.is_wall(nx, ny) {
+                        continue;
+                    }
+                    let neighbour = (ny * self.width as i64 + nx) as usize;
+                    if region[neighbour].is_none() {
+                        region[neighbour] = Some(id);
+                        stack.push((nx as u32, ny as u32));
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+        let largest = (0..sizes.len()).max_by_key(|id| sizes[*id]);
+        for (wall, region) in self.walls.iter_mut().zip(region) {
+            if region != largest {
+                *wall = true;
+            }
+        }
+    }
+}
+
+#[typetag::serde(name = "cave")]
+impl Map for CaveMap {
+    fn get_map_data(&self, generator: &mut Pcg64) -> MapData {
+        let width = self.width;
+        let height = self.height;
+        let interior = (width.saturating_sub(2) * height.saturating_sub(2)) as f32;
+
+        let mut best: Option<Cave> = None;
+        for _ in 0..MAX_ATTEMPTS {
+            let mut cave = Cave::random(width, height, self.fill_probability, generator);
+            for _ in 0..self.iterations {
+                cave.smooth(self.birth_threshold, self.survival_threshold);
+            }
+            cave.keep_largest_region();
+            let open = cave.open_count();
+            if best.as_ref().is_none_or(|best| open > best.open_count()) {
+                best = Some(cave);
+            }
+            if open as f32 >= interior * self.min_open_area as f32 / 100.0 {
+                break;
+            }
+        }
+        let cave = best.unwrap();
+
+        let mut cells: HashMap<(u32, u32), Cell> = cave
+            .positions()
+            .map(|(x, y)| {
+                let cell = if cave.is_wall(x as i64, y as i64) {
+                    Cell::Wall
+                } else {
+                    Cell::Empty
+                };
+                ((x, y), cell)
+            })
+            .collect();
+
+        // Spawns go where the snake has open space all around it and a clear run ahead,
+        // falling back to anywhere with at least one open cell ahead in cramped caves
+        let is_open = |x: i64, y: i64| !cave.is_wall(x, y);
+        let mut candidates: Vec<((u32, u32), Direction, bool)> = cave
+            .positions()
+            .filter(|&(x, y)| is_open(x as i64, y as i64))
+            .flat_map(|(x, y)| {
+                Direction::ALL.iter().filter_map(move |direction| {
+                    let (x, y) = (x as i64, y as i64);
+                    let (dx, dy) = MapData::offset(*direction);
+                    if !is_open(x + dx, y + dy) {
+                        return None;
+                    }
+                    let roomy = (-1..=1)
+                        .flat_map(|ox| (-1..=1).map(move |oy| (ox, oy)))
+                        .all(|(ox, oy)| is_open(x + ox, y + oy))
+                        && (1..=3).all(|ahead| is_open(x + dx * ahead, y + dy * ahead))
+                        && is_open(x - dx, y - dy);
+                    Some(((x as u32, y as u32), *direction, roomy))
+                })
+            })
+            .collect();
+        candidates.shuffle(generator);
+        candidates.sort_by_key(|(_, _, roomy)| !roomy);
+
+        let mut spawns: Vec<(u32, u32)> = Vec::new();
+        for spacing in [SPAWN_SPACING, 1] {
+            for &(position, direction, _) in &candidates {
+                if spawns.len() >= self.spawns as usize {
+                    break;
+                }
+                let too_close = spawns.iter().any(|spawn| {
+                    let distance = (spawn.0 as i64 - position.0 as i64).abs()
+                        + (spawn.1 as i64 - position.1 as i64).abs();
+                    distance < spacing as i64
+                });
+                if !too_close {
+                    spawns.push(position);
+                    cells.insert(position, Cell::Spawn(direction));
+                }
+            }
+        }
+
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
@@ -1,10 +1,6 @@
-use crate::{
-    config::{Cell, Map, MapData},
-    Direction,
-};
+use crate::config::{Map, MapData};
 
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize};
-use std::{collections::HashMap, convert::TryInto};
 
 #[derive(Deserialize, Serialize)]
 pub struct CustomMap {
@@ -39,47 +35,7 @@ where
         where
             E: serde::de::Error,
         {
-            fn to_u32_in_range<E: serde::de::Error>(value: usize, name: &str) -> Result<u32, E> {
-                value
-                    .try_into()
-                    .map_err(|_| E::custom(format!("{} dimension is too big", name)))
-            }
-
-            let mut cells = HashMap::new();
-            let mut width = 0u32;
-            let mut height = 0u32;
-
-            for (row, line) in value.lines().enumerate() {
-                let row = to_u32_in_range(row, "Vertical")?;
-
-                for (column, char) in line.chars().enumerate() {
-                    let column = to_u32_in_range(column, "Horizontal")?;
-
-                    cells.insert(
-                        (column as u32, row as u32),
-                        match char {
-                            '#' => Cell::Wall,
-                            '^' => Cell::Spawn(Direction::Up),
-                            'v' => Cell::Spawn(Direction::Down),
-                            '<' => Cell::Spawn(Direction::Left),
-                            '>' => Cell::Spawn(Direction::Right),
-                            ' ' => Cell::Empty,
-                            other => {
-                                return Err(E::custom(format!("Unknown cell type {:?}", other)))
-                            }
-                        },
-                    );
-
-                    width = width.max(column + 1);
-                    height = height.max(row + 1);
-                }
-            }
-
-            Ok(MapData {
-                width,
-                height,
-                cells,
-            })
+            value.parse().map_err(E::custom)
         }
     }
 
@@ -1,6 +1,6 @@
 use crate::{
     config::{Cell, Map, MapData},
-    Direction,
+    game::Direction,
 };
 
 use serde::{Deserialize, Serialize};
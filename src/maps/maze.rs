@@ -0,0 +1,241 @@
+use crate::{
+    config::{Cell, Map, MapData},
+    game::Direction,
+};
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct MazeMap {
+    pub width: u32,
+    pub height: u32,
+    corridor_width: u32,
+    braid: f32,
+    spawns: u32,
+}
+
+impl Default for MazeMap {
+    fn default() -> Self {
+        Self {
+            width: 31,
+            height: 22,
+            corridor_width: 2,
+            braid: 0.5,
+            spawns: 4,
+        }
+    }
+}
+
+/// The maze itself, before being laid out on the grid: a grid of rooms, each `corridor_width` wide,
+/// along with the passages leading out of each room.
+struct Maze {
+    columns: u32,
+    rows: u32,
+    passages: Vec<Vec<Direction>>,
+}
+
+impl Maze {
+    fn index(&self, (x, y): (u32, u32)) -> usize {
+        (y * self.columns + x) as usize
+    }
+
+    // Like map data, the y-axis points down, so up is towards row 0
+    fn neighbour(&self, (x, y): (u32, u32), direction: Direction) -> Option<(u32, u32)> {
+        let (dx, dy) = MapData::offset(direction);
+        let (x, y) = (x as i64 + dx, y as i64 + dy);
+        if x < 0 || y < 0 || x >= self.columns as i64 || y >= self.rows as i64 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    fn passages(&self, room: (u32, u32)) -> &[Direction] {
+        &self.passages[self.index(room)]
+    }
+
+    fn connect(&mut self, room: (u32, u32), direction: Direction) {
+        if let Some(neighbour) = self.neighbour(room, direction) {
+            let (room, neighbour) = (self.index(room), self.index(neighbour));
+            self.passages[room].push(direction);
+            self.passages[neighbour].push(direction.opposite());
+        }
+    }
+
+    fn rooms(&self) -> impl Iterator<Item = (u32, u32)> {
+        let columns = self.columns;
+        (0..self.rows).flat_map(move |y| (0..columns).map(move |x| (x, y)))
+    }
+
+    /// Carves a perfect maze, where there is exactly one way between any two rooms,
+    /// by walking in random directions and backtracking out of dead ends.
+    fn generate(columns: u32, rows: u32, generator: &mut Pcg64) -> Self {
+        let mut maze = Self {
+            columns,
+            rows,
+            passages: vec![Vec::new(); (columns * rows) as usize],
+        };
+        if columns == 0 || rows == 0 {
+            return maze;
+        }
+        let mut visited = vec![false; maze.passages.len()];
+        let start = (generator.gen_range(0..columns), generator.gen_range(0..rows));
+        visited[maze.index(start)] = true;
+        let mut stack = vec![start];
+        while let Some(&room) = stack.last() {
+            let unvisited: Vec<Direction> = Direction::ALL
+                .iter()
+                .copied()
+                .filter(|direction| {
+                    maze.neighbour(room, *direction)
+                        .is_some_and(|neighbour| !visited[maze.index(neighbour)])
+                })
+                .collect();
+            match unvisited.choose(generator) {
+                Some(&direction) => {
+                    maze.connect(room, direction);
+                    let next = maze.neighbour(room, direction).unwrap();
+                    visited[maze.index(next)] = true;
+                    stack.push(next);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        maze
+    }
+
+    /// Opens up each dead end with a chance of `braid`, adding loops to the maze.
+    fn braid(&mut self, braid: f32, generator: &mut Pcg64) {
+        let rooms: Vec<(u32, u32)> = self.rooms().collect();
+        for room in rooms {
+            if self.passages(room).len() != 1 || generator.gen::<f32>() >= braid {
+                continue;
+            }
+            let closed: Vec<Direction> = Direction::ALL
+                .iter()
+                .copied()
+                .filter(|direction| !self.passages(room).contains(direction))
+                .filter(|direction| self.neighbour(room, *direction).is_some())
+                .collect();
+            // Joining up with another dead end gets rid of two at once
+            let dead_ends: Vec<Direction> = closed
+                .iter()
+                .copied()
+                .filter(|direction| {
+                    let neighbour = self.neighbour(room, *direction).unwrap();
+                    self.passages(neighbour).len() == 1
+                })
+                .collect();
+            let options = if dead_ends.is_empty() {
+                closed
+            } else {
+                dead_ends
+            };
+            if let Some(&direction) = options.choose(generator) {
+                self.connect(room, direction);
+            }
+        }
+    }
+}
+
+#[typetag::serde(name = "maze")]
+impl Map for MazeMap {
+    fn get_map_data(&self, generator: &mut Pcg64) -> MapData {
+        let width = self.width;
+        let height = self.height;
+        // 1-wide corridors would leave a snake no room to turn around in dead ends
+        let corridor_width = self.corridor_width.max(2);
+        let step = corridor_width + 1;
+        let mut maze = Maze::generate(
+            width.saturating_sub(1) / step,
+            height.saturating_sub(1) / step,
+            generator,
+        );
+        maze.braid(self.braid, generator);
+
+        // The top left corner of a room on the grid
+        let corner = |(x, y): (u32, u32)| (1 + x * step, 1 + y * step);
+        let mut cells = HashMap::new();
+        for x in 0..width {
+            for y in 0..height {
+                cells.insert((x, y), Cell::Wall);
+            }
+        }
+        for room in maze.rooms() {
+            let (left, top) = corner(room);
+            for x in left..left + corridor_width {
+                for y in top..top + corridor_width {
+                    cells.insert((x, y), Cell::Empty);
+                }
+            }
+            for direction in maze.passages(room) {
+                // Passages are as wide as the rooms, through the wall on that side
+                let wall: Vec<(u32, u32)> = match direction {
+                    Direction::Left => (top..top + corridor_width)
+                        .map(|y| (left - 1, y))
+                        .collect(),
+                    Direction::Right => (top..top + corridor_width)
+                        .map(|y| (left + corridor_width, y))
+                        .collect(),
+                    Direction::Up => (left..left + corridor_width)
+                        .map(|x| (x, top - 1))
+                        .collect(),
+                    Direction::Down => (left..left + corridor_width)
+                        .map(|x| (x, top + corridor_width))
+                        .collect(),
+                };
+                for position in wall {
+                    cells.insert(position, Cell::Empty);
+                }
+            }
+        }
+
+        // Spawns go at the edge of a room, facing out through a passage,
+        // preferably where the corridor runs straight on behind them so the body has room
+        let mut candidates: Vec<((u32, u32), Direction)> = maze
+            .rooms()
+            .flat_map(|room| {
+                maze.passages(room)
+                    .iter()
+                    .map(move |direction| (room, *direction))
+            })
+            .collect();
+        candidates.shuffle(generator);
+        candidates.sort_by_key(|(room, direction)| {
+            !maze.passages(*room).contains(&direction.opposite())
+        });
+        let mut spawn_rooms = Vec::new();
+        for (room, direction) in candidates {
+            if spawn_rooms.len() >= self.spawns as usize {
+                break;
+            }
+            if spawn_rooms.contains(&room) {
+                continue;
+            }
+            spawn_rooms.push(room);
+            let (left, top) = corner(room);
+            let lane = corridor_width / 2;
+            let head = match direction {
+                Direction::Left => (left, top + lane),
+                Direction::Right => (left + corridor_width - 1, top + lane),
+                Direction::Up => (left + lane, top),
+                Direction::Down => (left + lane, top + corridor_width - 1),
+            };
+            cells.insert(head, Cell::Spawn(direction));
+        }
+
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
@@ -6,3 +6,9 @@ pub use default::DefaultMap;
 
 mod custom;
 pub use custom::CustomMap;
+
+mod maze;
+mod cave;
+mod rooms;
+mod symmetric;
+mod modified;
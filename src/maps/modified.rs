@@ -0,0 +1,40 @@
+use crate::{
+    config::{Map, MapData, Modifier},
+    maps::DefaultMap,
+};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ModifiedMap {
+    map: Box<dyn Map>,
+    modifiers: Vec<Box<dyn Modifier>>,
+}
+
+impl Default for ModifiedMap {
+    fn default() -> Self {
+        Self {
+            map: Box::new(DefaultMap::default()),
+            modifiers: Vec::new(),
+        }
+    }
+}
+
+#[typetag::serde(name = "modified")]
+impl Map for ModifiedMap {
+    fn get_map_data(&self, generator: &mut Pcg64) -> MapData {
+        let map_data = self.map.get_map_data(generator);
+        self.modifiers.iter().fold(map_data, |map_data, modifier| {
+            modifier.modify(map_data, generator)
+        })
+    }
+    fn get_dimensions(&self) -> (u32, u32) {
+        self.modifiers
+            .iter()
+            .fold(self.map.get_dimensions(), |dimensions, modifier| {
+                modifier.get_dimensions(dimensions)
+            })
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    config::{Cell, Map, MapData},
+    game::Direction,
+};
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct RoomsMap {
+    pub width: u32,
+    pub height: u32,
+    min_room_size: u32,
+    door_width: u32,
+    pillars: u32,
+}
+
+impl Default for RoomsMap {
+    fn default() -> Self {
+        Self {
+            width: 34,
+            height: 22,
+            min_room_size: 5,
+            door_width: 2,
+            pillars: 1,
+        }
+    }
+}
+
+/// A rectangle on the grid, including the walls around it.
+#[derive(Clone, Copy)]
+struct Room {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Room {
+    fn inner_width(&self) -> u32 {
+        (self.right - self.left).saturating_sub(1)
+    }
+
+    fn inner_height(&self) -> u32 {
+        (self.bottom - self.top).saturating_sub(1)
+    }
+}
+
+/// A wall dividing a room in two, which gets a doorway once the halves are divided further.
+struct Split {
+    room: Room,
+    vertical: bool,
+    at: u32,
+}
+
+impl RoomsMap {
+    /// Divides a room in two along its longer side while both halves can still fit a room,
+    /// then keeps dividing the halves.
+    fn partition(
+        &self,
+        room: Room,
+        generator: &mut Pcg64,
+        rooms: &mut Vec<Room>,
+        splits: &mut Vec<Split>,
+    ) {
+        let min_room_size = self.min_room_size.max(1);
+        let fits = |size: u32| size > min_room_size * 2;
+        let vertical = match (fits(room.inner_width()), fits(room.inner_height())) {
+            (false, false) => {
+                rooms.push(room);
+                return;
+            }
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) if room.inner_width() != room.inner_height() => {
+                room.inner_width() > room.inner_height()
+            }
+            (true, true) => generator.gen(),
+        };
+        let (start, end) = if vertical {
+            (room.left, room.right)
+        } else {
+            (room.top, room.bottom)
+        };
+        let at = generator.gen_range(start + min_room_size + 1..=end - min_room_size - 1);
+        let (first, second) = if vertical {
+            (Room { right: at, ..room }, Room { left: at, ..room })
+        } else {
+            (Room { bottom: at, ..room }, Room { top: at, ..room })
+        };
+        splits.push(Split { room, vertical, at });
+        self.partition(first, generator, rooms, splits);
+        self.partition(second, generator, rooms, splits);
+    }
+}
+
+#[typetag::serde(name = "rooms")]
+impl Map for RoomsMap {
+    fn get_map_data(&self, generator: &mut Pcg64) -> MapData {
+        let width = self.width;
+        let height = self.height;
+        let mut cells = HashMap::new();
+        for x in 0..width {
+            for y in 0..height {
+                cells.insert((x, y), Cell::Wall);
+            }
+        }
+        if width < 3 || height < 3 {
+            return MapData {
+                width,
+                height,
+                cells,
+            };
+        }
+
+        let mut rooms = Vec::new();
+        let mut splits = Vec::new();
+        let whole = Room {
+            left: 0,
+            top: 0,
+            right: width - 1,
+            bottom: height - 1,
+        };
+        self.partition(whole, generator, &mut rooms, &mut splits);
+        for room in &rooms {
+            for x in room.left + 1..room.right {
+                for y in room.top + 1..room.bottom {
+                    cells.insert((x, y), Cell::Empty);
+                }
+            }
+        }
+
+        // Doorways have to line up with open cells on both sides of the wall, which the halves'
+        // own walls might be in the way of. The first room on either side both start at the same
+        // end of the wall and are at least `min_room_size` long, so there's always somewhere for it.
+        let door_width = self.door_width.clamp(1, self.min_room_size.max(1));
+        let is_empty = |cells: &HashMap<(u32, u32), Cell>, position| {
+            matches!(cells.get(&position), Some(Cell::Empty))
+        };
+        for split in &splits {
+            let (start, end) = if split.vertical {
+                (split.room.top + 1, split.room.bottom)
+            } else {
+                (split.room.left + 1, split.room.right)
+            };
+            // Positions along the wall, and the cells on either side of it
+            let sides = |along: u32| {
+                if split.vertical {
+                    [
+                        (split.at - 1, along),
+                        (split.at, along),
+                        (split.at + 1, along),
+                    ]
+                } else {
+                    [
+                        (along, split.at - 1),
+                        (along, split.at),
+                        (along, split.at + 1),
+                    ]
+                }
+            };
+            let doorways: Vec<u32> = (start..end.saturating_sub(door_width - 1))
+                .filter(|&door| {
+                    (door..door + door_width).all(|along| {
+                        let [before, _, after] = sides(along);
+                        is_empty(&cells, before) && is_empty(&cells, after)
+                    })
+                })
+                .collect();
+            if let Some(&door) = doorways.choose(generator) {
+                for along in door..door + door_width {
+                    cells.insert(sides(along)[1], Cell::Empty);
+                }
+            }
+        }
+
+        // Every room gets a spawn in its middle, facing along its longer side
+        for room in &rooms {
+            if room.inner_width() == 0 || room.inner_height() == 0 {
+                continue;
+            }
+            let center = (
+                room.left + 1 + room.inner_width() / 2,
+                room.top + 1 + room.inner_height() / 2,
+            );
+            let directions = if room.inner_width() >= room.inner_height() {
+                [Direction::Left, Direction::Right]
+            } else {
+                [Direction::Up, Direction::Down]
+            };
+            let direction = *directions.choose(generator).unwrap();
+            cells.insert(center, Cell::Spawn(direction));
+
+            // Pillars keep a clear ring around them, away from the room's walls and each other,
+            // so they can't cut the room off. They also stay out of the spawn's way.
+            let mut candidates: Vec<(u32, u32)> = (room.left + 2..room.right.saturating_sub(1))
+                .flat_map(|x| (room.top + 2..room.bottom.saturating_sub(1)).map(move |y| (x, y)))
+                .filter(|&(x, y)| match direction {
+                    Direction::Left | Direction::Right => y != center.1,
+                    Direction::Up | Direction::Down => x != center.0,
+                })
+                .collect();
+            candidates.shuffle(generator);
+            let mut pillars: Vec<(u32, u32)> = Vec::new();
+            for (x, y) in candidates {
+                if pillars.len() >= self.pillars as usize {
+                    break;
+                }
+                let crowded = pillars.iter().any(|&(px, py)| {
+                    (px as i64 - x as i64).abs() <= 1 && (py as i64 - y as i64).abs() <= 1
+                });
+                if !crowded {
+                    pillars.push((x, y));
+                    cells.insert((x, y), Cell::Wall);
+                }
+            }
+        }
+
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
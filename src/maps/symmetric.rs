@@ -0,0 +1,134 @@
+use crate::{
+    config::{Cell, Map, MapData},
+    maps::CorridorsMap,
+};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct SymmetricMap {
+    symmetry: Symmetry,
+    map: Box<dyn Map>,
+}
+
+impl Default for SymmetricMap {
+    fn default() -> Self {
+        Self {
+            symmetry: Symmetry::Horizontal,
+            map: Box::new(CorridorsMap::default()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Symmetry {
+    /// Mirrored left to right.
+    Horizontal,
+    /// Mirrored top to bottom.
+    Vertical,
+    /// The same after half a turn.
+    HalfTurn,
+    /// The same after a quarter turn, which needs a square map.
+    QuarterTurn,
+}
+
+impl Symmetry {
+    /// How many ways the map can be turned or flipped while looking the same,
+    /// including leaving it as it is.
+    fn order(self) -> u32 {
+        match self {
+            Self::QuarterTurn => 4,
+            _ => 2,
+        }
+    }
+
+    /// Turns or flips the map the `n`th of those ways.
+    fn apply(self, map: &MapData, n: u32) -> MapData {
+        (0..n).fold(map.clone(), |map, _| match self {
+            Self::Horizontal => map.flipped_horizontally(),
+            Self::Vertical => map.flipped_vertically(),
+            Self::HalfTurn => map.flipped_horizontally().flipped_vertically(),
+            Self::QuarterTurn => map.rotated_clockwise(),
+        })
+    }
+
+    /// Whether a cell is in the part of the map that gets copied over the rest of it.
+    /// Turning or flipping that part every way covers the whole map exactly once,
+    /// apart from cells that end up where they started, like the middle column of a mirrored map.
+    fn is_kept(self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        match self {
+            Self::Horizontal => x < width.div_ceil(2),
+            Self::Vertical => y < height.div_ceil(2),
+            Self::HalfTurn => {
+                y < height / 2 || (height % 2 == 1 && y == height / 2 && x < width.div_ceil(2))
+            }
+            Self::QuarterTurn => {
+                (x < width.div_ceil(2) && y < height / 2)
+                    || (width % 2 == 1 && x == width / 2 && y == height / 2)
+            }
+        }
+    }
+}
+
+#[typetag::serde(name = "symmetric")]
+impl Map for SymmetricMap {
+    fn get_map_data(&self, generator: &mut Pcg64) -> MapData {
+        let (width, height) = self.get_dimensions();
+        let mut map_data = self.map.get_map_data(generator);
+        map_data.width = width;
+        map_data.height = height;
+        map_data.cells.retain(|(x, y), _| *x < width && *y < height);
+
+        // Start from whichever way around has the most spawns in the part that's kept,
+        // since the inner map might not have put any there
+        let spawns = |map_data: &MapData| {
+            map_data
+                .iter()
+                .filter(|(x, y, cell)| {
+                    matches!(cell, Cell::Spawn(_)) && self.symmetry.is_kept(*x, *y, width, height)
+                })
+                .count()
+        };
+        let mut kept = map_data.clone();
+        for n in 1..self.symmetry.order() {
+            let turned = self.symmetry.apply(&map_data, n);
+            if spawns(&turned) > spawns(&kept) {
+                kept = turned;
+            }
+        }
+        kept.cells
+            .retain(|(x, y), _| self.symmetry.is_kept(*x, *y, width, height));
+
+        let mut cells = HashMap::new();
+        for n in 0..self.symmetry.order() {
+            for (x, y, cell) in self.symmetry.apply(&kept, n).iter() {
+                let cell = match (cells.get(&(x, y)), cell) {
+                    (None, cell) => cell,
+                    // A spawn that ends up where it started would be facing two ways at once
+                    (Some(Cell::Spawn(first)), Cell::Spawn(second)) if first != &second => {
+                        Cell::Empty
+                    }
+                    (Some(first), _) => *first,
+                };
+                cells.insert((x, y), cell);
+            }
+        }
+
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self) -> (u32, u32) {
+        let (width, height) = self.map.get_dimensions();
+        match self.symmetry {
+            Symmetry::QuarterTurn => (width.min(height), width.min(height)),
+            _ => (width, height),
+        }
+    }
+}
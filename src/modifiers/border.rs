@@ -0,0 +1,45 @@
+use crate::config::{Cell, MapData, Modifier};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// Walls off the edges of the map, or opens them up with `remove`, e.g. for wrapping maps.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct BorderModifier {
+    thickness: u32,
+    remove: bool,
+}
+
+impl Default for BorderModifier {
+    fn default() -> Self {
+        Self {
+            thickness: 1,
+            remove: false,
+        }
+    }
+}
+
+#[typetag::serde(name = "border")]
+impl Modifier for BorderModifier {
+    fn modify(&self, mut map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        let (width, height) = (map_data.width, map_data.height);
+        let thickness = self.thickness;
+        for x in 0..width {
+            for y in 0..height {
+                if x < thickness
+                    || y < thickness
+                    || x >= width.saturating_sub(thickness)
+                    || y >= height.saturating_sub(thickness)
+                {
+                    let cell = if self.remove { Cell::Empty } else { Cell::Wall };
+                    map_data.cells.insert((x, y), cell);
+                }
+            }
+        }
+        map_data
+    }
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        dimensions
+    }
+}
@@ -0,0 +1,28 @@
+use crate::config::{Cell, MapData, Modifier};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// Turns walls into empty space and empty space into walls, leaving spawns as they are.
+#[derive(Default, Deserialize, Serialize)]
+pub struct InvertModifier {}
+
+#[typetag::serde(name = "invert")]
+impl Modifier for InvertModifier {
+    fn modify(&self, mut map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        for x in 0..map_data.width {
+            for y in 0..map_data.height {
+                let cell = match map_data.cells.get(&(x, y)) {
+                    Some(Cell::Wall) => Cell::Empty,
+                    Some(Cell::Spawn(direction)) => Cell::Spawn(*direction),
+                    Some(Cell::Empty) | None => Cell::Wall,
+                };
+                map_data.cells.insert((x, y), cell);
+            }
+        }
+        map_data
+    }
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        dimensions
+    }
+}
@@ -0,0 +1,6 @@
+mod border;
+mod invert;
+mod obstacles;
+mod resize;
+mod spawns;
+mod transform;
@@ -0,0 +1,55 @@
+use crate::config::{Cell, MapData, Modifier};
+
+use rand::prelude::*;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// How many places to try for each obstacle before giving up on it.
+const ATTEMPTS_PER_OBSTACLE: u32 = 20;
+
+/// Drops square blocks of wall onto empty parts of the map.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScatterObstaclesModifier {
+    count: u32,
+    size: u32,
+}
+
+impl Default for ScatterObstaclesModifier {
+    fn default() -> Self {
+        Self { count: 4, size: 2 }
+    }
+}
+
+#[typetag::serde(name = "scatter_obstacles")]
+impl Modifier for ScatterObstaclesModifier {
+    fn modify(&self, mut map_data: MapData, generator: &mut Pcg64) -> MapData {
+        let size = self.size.max(1);
+        if size > map_data.width || size > map_data.height {
+            return map_data;
+        }
+        for _ in 0..self.count {
+            for _ in 0..ATTEMPTS_PER_OBSTACLE {
+                let left = generator.gen_range(0..=map_data.width - size);
+                let top = generator.gen_range(0..=map_data.height - size);
+                let block: Vec<(u32, u32)> = (left..left + size)
+                    .flat_map(|x| (top..top + size).map(move |y| (x, y)))
+                    .collect();
+                // Only ever on empty space, so spawns and the map's own walls stay as they are
+                let is_empty = block.iter().all(|position| {
+                    matches!(map_data.cells.get(position), Some(Cell::Empty) | None)
+                });
+                if is_empty {
+                    for position in block {
+                        map_data.cells.insert(position, Cell::Wall);
+                    }
+                    break;
+                }
+            }
+        }
+        map_data
+    }
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        dimensions
+    }
+}
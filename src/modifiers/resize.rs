@@ -0,0 +1,85 @@
+use crate::config::{Cell, MapData, Modifier};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Cuts rows and columns off the edges of the map.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CropModifier {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+#[typetag::serde(name = "crop")]
+impl Modifier for CropModifier {
+    fn modify(&self, map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        let (width, height) = self.get_dimensions((map_data.width, map_data.height));
+        let cells = map_data
+            .iter()
+            .filter(|(x, y, _)| {
+                *x >= self.left && *y >= self.top && x - self.left < width && y - self.top < height
+            })
+            .map(|(x, y, cell)| ((x - self.left, y - self.top), cell))
+            .collect();
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            width.saturating_sub(self.left + self.right),
+            height.saturating_sub(self.top + self.bottom),
+        )
+    }
+}
+
+/// Adds rows and columns of empty space, or walls with `walls`, to the edges of the map.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PadModifier {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+    walls: bool,
+}
+
+#[typetag::serde(name = "pad")]
+impl Modifier for PadModifier {
+    fn modify(&self, map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        let (width, height) = self.get_dimensions((map_data.width, map_data.height));
+        let fill = if self.walls { Cell::Wall } else { Cell::Empty };
+        let mut cells = HashMap::new();
+        for x in 0..width {
+            for y in 0..height {
+                cells.insert((x, y), fill);
+            }
+        }
+        // Cells missing from the original map are empty, not part of the padding
+        for x in 0..map_data.width {
+            for y in 0..map_data.height {
+                cells.insert((x + self.left, y + self.top), Cell::Empty);
+            }
+        }
+        for (x, y, cell) in map_data.iter() {
+            cells.insert((x + self.left, y + self.top), cell);
+        }
+        MapData {
+            width,
+            height,
+            cells,
+        }
+    }
+    fn get_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            width + self.left + self.right,
+            height + self.top + self.bottom,
+        )
+    }
+}
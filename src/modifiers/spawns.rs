@@ -0,0 +1,58 @@
+use crate::config::{Cell, MapData, Modifier};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// Knocks down walls around each spawn, and in a line ahead of it,
+/// so that snakes don't start out boxed in.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClearAroundSpawnsModifier {
+    radius: u32,
+    ahead: u32,
+}
+
+impl Default for ClearAroundSpawnsModifier {
+    fn default() -> Self {
+        Self {
+            radius: 1,
+            ahead: 3,
+        }
+    }
+}
+
+#[typetag::serde(name = "clear_around_spawns")]
+impl Modifier for ClearAroundSpawnsModifier {
+    fn modify(&self, mut map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        let spawns: Vec<(u32, u32, Cell)> = map_data
+            .iter()
+            .filter(|(_, _, cell)| matches!(cell, Cell::Spawn(_)))
+            .collect();
+        let (width, height) = (map_data.width as i64, map_data.height as i64);
+        let radius = self.radius as i64;
+        for (x, y, cell) in spawns {
+            let (x, y) = (x as i64, y as i64);
+            let mut cleared: Vec<(i64, i64)> = (-radius..=radius)
+                .flat_map(|dx| (-radius..=radius).map(move |dy| (x + dx, y + dy)))
+                .collect();
+            if let Cell::Spawn(direction) = cell {
+                let (dx, dy) = MapData::offset(direction);
+                cleared
+                    .extend((1..=self.ahead as i64).map(|ahead| (x + dx * ahead, y + dy * ahead)));
+            }
+            for (x, y) in cleared {
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    continue;
+                }
+                let position = (x as u32, y as u32);
+                if let Some(Cell::Wall) = map_data.cells.get(&position) {
+                    map_data.cells.insert(position, Cell::Empty);
+                }
+            }
+        }
+        map_data
+    }
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        dimensions
+    }
+}
@@ -0,0 +1,64 @@
+use crate::config::{MapData, Modifier};
+
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+/// Turns the map clockwise by a number of quarter turns.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct RotateModifier {
+    turns: u32,
+}
+
+impl Default for RotateModifier {
+    fn default() -> Self {
+        Self { turns: 1 }
+    }
+}
+
+#[typetag::serde(name = "rotate")]
+impl Modifier for RotateModifier {
+    fn modify(&self, map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        (0..self.turns % 4).fold(map_data, |map_data, _| map_data.rotated_clockwise())
+    }
+    fn get_dimensions(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        if self.turns % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+/// Mirrors the map left to right, top to bottom, or both.
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct FlipModifier {
+    horizontal: bool,
+    vertical: bool,
+}
+
+impl Default for FlipModifier {
+    fn default() -> Self {
+        Self {
+            horizontal: true,
+            vertical: false,
+        }
+    }
+}
+
+#[typetag::serde(name = "flip")]
+impl Modifier for FlipModifier {
+    fn modify(&self, mut map_data: MapData, _generator: &mut Pcg64) -> MapData {
+        if self.horizontal {
+            map_data = map_data.flipped_horizontally();
+        }
+        if self.vertical {
+            map_data = map_data.flipped_vertically();
+        }
+        map_data
+    }
+    fn get_dimensions(&self, dimensions: (u32, u32)) -> (u32, u32) {
+        dimensions
+    }
+}
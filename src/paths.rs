@@ -0,0 +1,78 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Where configuration, themes and sounds are looked up, in order:
+/// the user's configuration directory, then the directory Hebi is installed in.
+/// Files in the user's directory take precedence, so a user theme replaces a bundled theme of the same name.
+pub struct SearchPaths {
+    /// `$XDG_CONFIG_HOME/hebi`, or `~/.config/hebi` if that isn't set.
+    user: Option<PathBuf>,
+    /// The directory next to the executable, or the crate root when launched through `cargo run`,
+    /// the same directory Bevy loads its assets from.
+    bundled: Option<PathBuf>,
+    /// `$XDG_DATA_HOME/hebi`, or `~/.local/share/hebi`, for files Hebi writes itself.
+    data: Option<PathBuf>,
+}
+
+impl SearchPaths {
+    pub fn new() -> Self {
+        let user = xdg_directory("XDG_CONFIG_HOME", ".config");
+        let bundled = env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).or_else(|| {
+            env::current_exe()
+                .ok()
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+        });
+        let data = xdg_directory("XDG_DATA_HOME", ".local/share");
+        Self {
+            user,
+            bundled,
+            data,
+        }
+    }
+
+    pub fn config(&self) -> Option<PathBuf> {
+        self.find("config.toml", "config.toml")
+    }
+
+    pub fn theme(&self, name: &str) -> Option<PathBuf> {
+        let file = format!("themes/{}.toml", name);
+        self.find(&file, &file)
+    }
+
+    pub fn sound(&self, name: &str) -> Option<PathBuf> {
+        self.find(
+            &format!("sounds/{}", name),
+            &format!("assets/sounds/{}", name),
+        )
+    }
+
+    /// Where high scores are kept, falling back to next to the executable without a home directory.
+    pub fn high_scores(&self) -> Option<PathBuf> {
+        self.data
+            .as_ref()
+            .or(self.bundled.as_ref())
+            .map(|directory| directory.join("high_scores.toml"))
+    }
+
+    /// Every directory searched, for telling the user where files can go.
+    pub fn directories(&self) -> impl Iterator<Item = &Path> {
+        self.user.iter().chain(self.bundled.iter()).map(PathBuf::as_path)
+    }
+
+    fn find(&self, user: &str, bundled: &str) -> Option<PathBuf> {
+        let user = self.user.as_ref().map(|directory| directory.join(user));
+        let bundled = self.bundled.as_ref().map(|directory| directory.join(bundled));
+        user.into_iter().chain(bundled).find(|path| path.is_file())
+    }
+}
+
+/// `$variable/hebi` if set, otherwise `fallback` in the home directory, as the XDG base directory spec describes.
+fn xdg_directory(variable: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|directory| directory.join("hebi"))
+}
@@ -0,0 +1,103 @@
+use crate::{
+    config::{Config, MapData},
+    game::{Direction, Input},
+};
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Everything needed to play back a game exactly: the configuration it was played with
+/// (including the seed) and every input, keyed by the tick it was applied on.
+#[derive(Deserialize, Serialize)]
+pub struct Replay {
+    // Before the configuration table, since TOML can't write a value such as an empty list after a table
+    pub inputs: Vec<RecordedInput>,
+    pub config: toml::Value,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RecordedInput {
+    pub tick: u64,
+    pub snake: usize,
+    pub direction: Direction,
+}
+
+impl Replay {
+    /// Starts recording a game played with the given configuration on the given map. The configuration is
+    /// recorded as it was resolved, with every default filled in, and the map as it was generated,
+    /// so that the replay plays out the same even if the defaults or the map generators change.
+    pub fn new(config: &Config, map_data: &MapData) -> Result<Self, String> {
+        let config = toml::Value::try_from(config).map_err(|error| {
+            format!("Failed to serialize the configuration for the replay: {}", error)
+        })?;
+        let mut replay = Self {
+            inputs: Vec::new(),
+            config,
+        };
+        replay.set_map(map_data);
+        Ok(replay)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to load replay {:?}: {}", path, error))?;
+        let mut replay: Self = toml::from_str(&contents)
+            .map_err(|error| format!("Failed to parse replay {:?}: {}", path, error))?;
+        replay.inputs.sort_by_key(|input| input.tick);
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = toml::to_string(self)
+            .map_err(|error| format!("Failed to serialize replay: {}", error))?;
+        fs::write(path, contents)
+            .map_err(|error| format!("Failed to save replay {:?}: {}", path, error))
+    }
+
+    /// Starts recording over for a new game, played with the same configuration and the given seed and map.
+    pub fn restart(&mut self, seed: u64, map_data: &MapData) {
+        if let toml::Value::Table(config) = &mut self.config {
+            config.insert("seed".into(), toml::Value::Integer(seed as i64));
+        }
+        self.set_map(map_data);
+        self.inputs.clear();
+    }
+
+    /// Records the map as a custom map.
+    fn set_map(&mut self, map_data: &MapData) {
+        if let toml::Value::Table(config) = &mut self.config {
+            let mut map = toml::value::Table::new();
+            map.insert("type".into(), toml::Value::String("custom".into()));
+            map.insert("data".into(), toml::Value::String(map_data.to_string()));
+            config.insert("map".into(), toml::Value::Table(map));
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, inputs: &[Input]) {
+        self.inputs
+            .extend(inputs.iter().map(|input| RecordedInput {
+                tick,
+                snake: input.snake,
+                direction: input.direction,
+            }));
+    }
+
+    pub fn inputs_at(&self, tick: u64) -> Vec<Input> {
+        let start = self.inputs.partition_point(|input| input.tick < tick);
+        self.inputs[start..]
+            .iter()
+            .take_while(|input| input.tick == tick)
+            .map(|input| Input {
+                snake: input.snake,
+                direction: input.direction,
+            })
+            .collect()
+    }
+}
+
+/// Whether the current game is being recorded, played back from a replay, or neither.
+pub enum ReplayMode {
+    Off,
+    Recording { path: String, replay: Replay },
+    Playback(Replay),
+}
@@ -0,0 +1,174 @@
+use crate::config::Config;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How many of the best runs are kept for each combination of settings.
+const KEPT_RUNS: usize = 10;
+
+/// The best runs for every combination of gameplay settings played, saved between sessions.
+/// Tables are keyed by a fingerprint of their settings, so scores are only compared to runs played the same way.
+#[derive(Default, Serialize)]
+pub struct HighScores {
+    tables: BTreeMap<String, ScoreTable>,
+}
+
+#[derive(Serialize)]
+pub struct ScoreTable {
+    /// The settings fingerprinted, kept so that the file can be made sense of.
+    pub settings: toml::Value,
+    pub runs: Vec<Run>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Run {
+    pub score: u32,
+    pub length: usize,
+    /// UTC date the run ended on, as `YYYY-MM-DD`.
+    pub date: String,
+    pub seed: u64,
+    /// In seconds.
+    pub duration: f64,
+}
+
+impl HighScores {
+    /// Loads the high scores, starting from scratch if there are none yet.
+    /// Runs that can't be read are skipped with a warning, so that one of them can't lose all the others.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(error) => return Err(format!("Failed to load high scores {:?}: {}", path, error)),
+        };
+        let file: toml::Value = toml::from_str(&contents)
+            .map_err(|error| format!("Failed to parse high scores {:?}: {}", path, error))?;
+        let mut high_scores = Self::default();
+        let tables = file.get("tables").and_then(toml::Value::as_table);
+        for (fingerprint, table) in tables.into_iter().flatten() {
+            let settings = match table.get("settings") {
+                Some(settings) => settings.clone(),
+                None => continue,
+            };
+            let runs = table.get("runs").and_then(toml::Value::as_array);
+            let runs = runs
+                .into_iter()
+                .flatten()
+                .filter_map(|run| match run.clone().try_into() {
+                    Ok(run) => Some(run),
+                    Err(error) => {
+                        eprintln!(
+                            "Skipping a high score that can't be read in {:?}: {}",
+                            path, error
+                        );
+                        None
+                    }
+                })
+                .collect();
+            high_scores
+                .tables
+                .insert(fingerprint.clone(), ScoreTable { settings, runs });
+        }
+        Ok(high_scores)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string(self)
+            .map_err(|error| format!("Failed to serialize high scores: {}", error))?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|error| {
+                format!("Failed to create directory {:?}: {}", directory, error)
+            })?;
+        }
+        fs::write(path, contents)
+            .map_err(|error| format!("Failed to save high scores {:?}: {}", path, error))
+    }
+
+    pub fn table(&self, settings: &toml::value::Table) -> Option<&ScoreTable> {
+        self.tables.get(&fingerprint(settings))
+    }
+
+    /// Adds a run to the table for its settings,
+    /// returning its 1-based place if it is one of the best runs.
+    pub fn record(&mut self, settings: &toml::value::Table, run: Run) -> Option<usize> {
+        let table = self
+            .tables
+            .entry(fingerprint(settings))
+            .or_insert_with(|| ScoreTable {
+                settings: toml::Value::Table(settings.clone()),
+                runs: Vec::new(),
+            });
+        // Ties go to the run that got there first
+        let place = table
+            .runs
+            .iter()
+            .take_while(|other| other.score >= run.score)
+            .count();
+        if place >= KEPT_RUNS {
+            return None;
+        }
+        table.runs.insert(place, run);
+        table.runs.truncate(KEPT_RUNS);
+        Some(place + 1)
+    }
+}
+
+/// The settings that change how hard the game is, which scores are only compared within.
+pub fn gameplay_settings(config: &Config) -> toml::value::Table {
+    let mut settings = toml::value::Table::new();
+    // Serialized from the parsed map rather than copied from the file, so that defaults are filled in
+    if let Ok(map) = toml::Value::try_from(&config.map) {
+        settings.insert("map".into(), map);
+    }
+    settings.insert("tick_length".into(), config.tick_length.into());
+    settings.insert("food_ticks".into(), config.food_ticks.into());
+    settings.insert(
+        "snake_spawn_segments".into(),
+        config.snake_spawn_segments.into(),
+    );
+    settings.insert("wrap".into(), config.wrap.into());
+    // Sharing the board changes the game, so solo runs don't compete with runs against others
+    settings.insert("players".into(), (config.players.len() as i64).into());
+    if let Ok(ai) = toml::Value::try_from(&config.ai) {
+        settings.insert("ai".into(), ai);
+    }
+    settings
+}
+
+/// A 64-bit FNV-1a hash of the settings, which unlike `DefaultHasher` is the same across Rust versions.
+fn fingerprint(settings: &toml::value::Table) -> String {
+    // Tables are sorted by key, so the same settings always serialize the same way
+    let serialized = toml::to_string(&toml::Value::Table(settings.clone())).unwrap_or_default();
+    let hash = serialized.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    // Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar,
+    // counting years from March so that leap days fall at the end of the year
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,202 @@
+use crate::{
+    config::{Cell, MapData},
+    game::Direction,
+};
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// Something wrong with a map, found before a game is started on it.
+/// Positions are given as 1-based lines and columns of the custom map text format.
+pub enum MapIssue {
+    Empty,
+    NoSpawns,
+    NoUsableSpawns,
+    SpawnBlocked { x: u32, y: u32, segments: u32 },
+    SpawnFacingWall { x: u32, y: u32 },
+    UnreachableRegion { x: u32, y: u32, size: usize },
+    DeadEnd { x: u32, y: u32 },
+}
+
+impl MapIssue {
+    /// Errors make the map unplayable, warnings only make it unpleasant to play.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Empty | Self::NoSpawns | Self::NoUsableSpawns)
+    }
+}
+
+impl fmt::Display for MapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let at = |x: &u32, y: &u32| format!("line {}, column {}", y + 1, x + 1);
+        match self {
+            Self::Empty => write!(f, "The map is empty"),
+            Self::NoSpawns => write!(
+                f,
+                "The map has no spawn points, add at least one of '^', 'v', '<' or '>'"
+            ),
+            Self::NoUsableSpawns => write!(f, "None of the map's spawn points can be used"),
+            Self::SpawnBlocked { x, y, segments } => write!(
+                f,
+                "The spawn point at {} has no room behind it for {} segments and will never be used",
+                at(x, y),
+                segments
+            ),
+            Self::SpawnFacingWall { x, y } => write!(
+                f,
+                "The spawn point at {} faces straight into a wall",
+                at(x, y)
+            ),
+            Self::UnreachableRegion { x, y, size } => write!(
+                f,
+                "{} empty cells around {} can't be reached from any spawn point",
+                size,
+                at(x, y)
+            ),
+            Self::DeadEnd { x, y } => write!(
+                f,
+                "Dead end at {}, a snake going in can't turn back out",
+                at(x, y)
+            ),
+        }
+    }
+}
+
+struct MapView<'a> {
+    map_data: &'a MapData,
+    wrap: bool,
+}
+
+impl MapView<'_> {
+    fn cell(&self, (x, y): (u32, u32)) -> Cell {
+        self.map_data.cells.get(&(x, y)).copied().unwrap_or(Cell::Empty)
+    }
+
+    fn is_open(&self, position: (u32, u32)) -> bool {
+        !matches!(self.cell(position), Cell::Wall)
+    }
+
+    fn neighbour(&self, (x, y): (u32, u32), direction: Direction) -> Option<(u32, u32)> {
+        let (dx, dy) = MapData::offset(direction);
+        let (x, y) = (x as i64 + dx, y as i64 + dy);
+        let (width, height) = (self.map_data.width as i64, self.map_data.height as i64);
+        if self.wrap {
+            return Some((x.rem_euclid(width) as u32, y.rem_euclid(height) as u32));
+        }
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    fn open_neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        Direction::ALL
+            .iter()
+            .filter_map(|direction| self.neighbour(position, *direction))
+            .filter(|neighbour| self.is_open(*neighbour))
+            .collect()
+    }
+}
+
+/// Checks a map for problems that would crash the game or make parts of it unplayable.
+pub fn validate_map(map_data: &MapData, snake_spawn_segments: u32, wrap: bool) -> Vec<MapIssue> {
+    let mut issues = Vec::new();
+    if map_data.width == 0 || map_data.height == 0 {
+        issues.push(MapIssue::Empty);
+        return issues;
+    }
+    let map = MapView { map_data, wrap };
+
+    // Row by row, so issues are reported in reading order
+    let cells: Vec<(u32, u32)> = (0..map_data.height)
+        .flat_map(|y| (0..map_data.width).map(move |x| (x, y)))
+        .collect();
+
+    let spawns: Vec<((u32, u32), Direction)> = cells
+        .iter()
+        .filter_map(|position| match map.cell(*position) {
+            Cell::Spawn(direction) => Some((*position, direction)),
+            _ => None,
+        })
+        .collect();
+    if spawns.is_empty() {
+        issues.push(MapIssue::NoSpawns);
+        return issues;
+    }
+
+    let mut usable_spawns = 0;
+    for ((x, y), direction) in spawns.iter() {
+        let mut position = (*x, *y);
+        let mut blocked = false;
+        for _ in 1..snake_spawn_segments {
+            match map.neighbour(position, direction.opposite()) {
+                Some(behind) if map.is_open(behind) => position = behind,
+                _ => {
+                    blocked = true;
+                    break;
+                }
+            }
+        }
+        if blocked {
+            issues.push(MapIssue::SpawnBlocked {
+                x: *x,
+                y: *y,
+                segments: snake_spawn_segments,
+            });
+            continue;
+        }
+        usable_spawns += 1;
+        match map.neighbour((*x, *y), *direction) {
+            Some(ahead) if map.is_open(ahead) => {}
+            _ => issues.push(MapIssue::SpawnFacingWall { x: *x, y: *y }),
+        }
+    }
+    if usable_spawns == 0 {
+        issues.push(MapIssue::NoUsableSpawns);
+    }
+
+    // Flood fill from every spawn, then group whatever is left into unreachable regions
+    let mut reached = HashSet::new();
+    let mut queue: VecDeque<(u32, u32)> = spawns.iter().map(|(position, _)| *position).collect();
+    reached.extend(queue.iter().copied());
+    while let Some(position) = queue.pop_front() {
+        for neighbour in map.open_neighbours(position) {
+            if reached.insert(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    for position in cells.iter() {
+        if !map.is_open(*position) || reached.contains(position) {
+            continue;
+        }
+        let mut size = 0;
+        queue.push_back(*position);
+        reached.insert(*position);
+        while let Some(position) = queue.pop_front() {
+            size += 1;
+            for neighbour in map.open_neighbours(position) {
+                if reached.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        issues.push(MapIssue::UnreachableRegion {
+            x: position.0,
+            y: position.1,
+            size,
+        });
+    }
+
+    // A cell with a single way in is the end of a 1-wide corridor,
+    // since a snake can't turn around on the spot
+    for position in cells.iter() {
+        if map.is_open(*position) && map.open_neighbours(*position).len() == 1 {
+            issues.push(MapIssue::DeadEnd {
+                x: position.0,
+                y: position.1,
+            });
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,28 @@
+use std::{fs, time::SystemTime};
+
+/// Notices changes to a file by polling its modification time.
+pub struct FileWatcher {
+    pub path: String,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(path: String) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    /// Whether the file was modified, created or deleted since the last call.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}